@@ -0,0 +1,283 @@
+use std::ops::{Add, Mul, Range, Sub};
+
+use crate::{
+    background::sky::Sky,
+    camera::{Camera, CameraBuilder},
+    cancellation::RenderLimits,
+    hittable::Hittable,
+    progress::ProgressSink,
+    vec3::Vec3,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    CatmullRom,
+}
+
+/// Values that can be blended between keyframes
+pub trait Keyframe:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f64, Output = Self>
+{
+}
+
+impl<T> Keyframe for T where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T> {}
+
+/// A value keyframed over time (in seconds), held constant before the first and after the last key
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    keys: Vec<(f64, T)>,
+    interpolation: Interpolation,
+}
+
+impl<T: Keyframe> Track<T> {
+    pub fn new(time: f64, value: T, interpolation: Interpolation) -> Self {
+        Self {
+            keys: vec![(time, value)],
+            interpolation,
+        }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self::new(0.0, value, Interpolation::Linear)
+    }
+
+    pub fn key(mut self, time: f64, value: T) -> Self {
+        let index = self.keys.partition_point(|(key_time, _)| *key_time <= time);
+        self.keys.insert(index, (time, value));
+
+        self
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.keys.len() == 1
+    }
+
+    pub fn sample(&self, time: f64) -> T {
+        let last = self.keys.len() - 1;
+
+        if time <= self.keys[0].0 {
+            return self.keys[0].1;
+        }
+        if time >= self.keys[last].0 {
+            return self.keys[last].1;
+        }
+
+        // Index of the key starting the segment containing time
+        let i = self.keys.partition_point(|(key_time, _)| *key_time <= time) - 1;
+
+        let (t1, p1) = self.keys[i];
+        let (t2, p2) = self.keys[i + 1];
+        let s = (time - t1) / (t2 - t1);
+
+        match self.interpolation {
+            Interpolation::Linear => p1 + (p2 - p1) * s,
+            Interpolation::CatmullRom => {
+                // Missing neighbours at the ends are duplicated
+                let p0 = self.keys[i.saturating_sub(1)].1;
+                let p3 = self.keys[(i + 2).min(last)].1;
+
+                let a = p1 * 2.0;
+                let b = (p2 - p0) * s;
+                let c = (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * (s * s);
+                let d = (p1 * 3.0 - p2 * 3.0 + p3 - p0) * (s * s * s);
+
+                (a + b + c + d) * 0.5
+            }
+        }
+    }
+
+    /// Points whose convex hull contains every value of the track
+    pub fn control_points(&self) -> Vec<T> {
+        let last = self.keys.len() - 1;
+        let mut points = vec![self.keys[0].1];
+
+        for i in 0..last {
+            let (p1, p2) = (self.keys[i].1, self.keys[i + 1].1);
+
+            // Catmull-Rom segments as cubic Béziers, which stay within their control points
+            if self.interpolation == Interpolation::CatmullRom {
+                let p0 = self.keys[i.saturating_sub(1)].1;
+                let p3 = self.keys[(i + 2).min(last)].1;
+
+                points.push(p1 + (p2 - p0) * (1.0 / 6.0));
+                points.push(p2 - (p3 - p1) * (1.0 / 6.0));
+            }
+
+            points.push(p2);
+        }
+
+        points
+    }
+}
+
+/// Keyframed camera parameters on top of a base camera configuration
+#[derive(Clone)]
+pub struct CameraAnimation {
+    builder: CameraBuilder,
+    look_from: Option<Track<Vec3>>,
+    look_at: Option<Track<Vec3>>,
+    vfov: Option<Track<f64>>,
+    // Sky background with the sun following a track of local solar time in hours
+    sky: Option<(Sky, Track<f64>)>,
+    frames_per_second: f64,
+    // Fraction of the frame the shutter stays open for
+    shutter_fraction: f64,
+}
+
+impl CameraAnimation {
+    pub fn new(builder: CameraBuilder) -> Self {
+        Self {
+            builder,
+            look_from: None,
+            look_at: None,
+            vfov: None,
+            sky: None,
+            frames_per_second: 24.0,
+            shutter_fraction: 0.5,
+        }
+    }
+
+    pub fn look_from(mut self, look_from: Track<Vec3>) -> Self {
+        self.look_from = Some(look_from);
+        self
+    }
+
+    pub fn look_at(mut self, look_at: Track<Vec3>) -> Self {
+        self.look_at = Some(look_at);
+        self
+    }
+
+    pub fn vfov(mut self, vfov: Track<f64>) -> Self {
+        self.vfov = Some(vfov);
+        self
+    }
+
+    /// Uses `sky` as the background, moving its sun through the day as `hours` changes
+    pub fn sky(mut self, sky: Sky, hours: Track<f64>) -> Self {
+        self.sky = Some((sky, hours));
+        self
+    }
+
+    pub fn frames_per_second(mut self, frames_per_second: f64) -> Self {
+        self.frames_per_second = frames_per_second;
+        self
+    }
+
+    pub fn shutter_fraction(mut self, shutter_fraction: f64) -> Self {
+        self.shutter_fraction = shutter_fraction;
+        self
+    }
+
+    pub fn frame_time(&self, frame: usize) -> f64 {
+        frame as f64 / self.frames_per_second
+    }
+
+    pub fn camera_at_frame(&self, frame: usize) -> Result<Camera, String> {
+        let time = self.frame_time(frame);
+        let mut builder = self.builder.clone();
+
+        if let Some(look_from) = &self.look_from {
+            builder = builder.look_from(look_from.sample(time));
+        }
+        if let Some(look_at) = &self.look_at {
+            builder = builder.look_at(look_at.sample(time));
+        }
+        if let Some(vfov) = &self.vfov {
+            builder = builder.vfov(vfov.sample(time));
+        }
+        if let Some((sky, hours)) = &self.sky {
+            builder = builder.background(sky.at_hour(hours.sample(time)));
+        }
+
+        builder
+            .shutter(time, time + self.shutter_fraction / self.frames_per_second)
+            .build()
+    }
+
+    /// Renders each frame to `{prefix}_{frame:04}.png`
+    ///
+    /// `limits` applies to each frame separately, cancelling also skips the remaining frames
+    pub fn render_frames<H: Hittable + Sync>(
+        &self,
+        world: &H,
+        frames: Range<usize>,
+        prefix: &str,
+        progress: &dyn ProgressSink,
+        limits: &RenderLimits,
+    ) -> Result<(), String> {
+        for frame in frames {
+            if limits.is_cancelled() {
+                break;
+            }
+
+            let camera = self.camera_at_frame(frame)?;
+
+            camera
+                .render(world, progress, limits)
+                .image
+                .save_png(&format!("{}_{:04}", prefix, frame))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracks() -> [Track<f64>; 2] {
+        [Interpolation::Linear, Interpolation::CatmullRom].map(|interpolation| {
+            Track::new(0.0, 1.0, interpolation)
+                .key(1.0, 5.0)
+                .key(1.5, -2.0)
+                .key(3.0, 0.0)
+        })
+    }
+
+    #[test]
+    fn sample_hits_keys_exactly() {
+        for track in tracks() {
+            for (time, value) in [(0.0, 1.0), (1.0, 5.0), (1.5, -2.0), (3.0, 0.0)] {
+                assert_eq!(track.sample(time), value);
+            }
+        }
+    }
+
+    #[test]
+    fn sample_holds_outside_keys() {
+        for track in tracks() {
+            assert_eq!(track.sample(-1.0), 1.0);
+            assert_eq!(track.sample(10.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn keys_are_sorted_by_time() {
+        let track = Track::constant(0.0).key(2.0, 2.0).key(1.0, 1.0);
+
+        assert_eq!(track.sample(1.0), 1.0);
+        assert_eq!(track.sample(1.5), 1.5);
+    }
+
+    #[test]
+    fn control_points_bound_the_track() {
+        for track in tracks() {
+            let points = track.control_points();
+            let min = points.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = points.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+            for i in 0..=3000 {
+                let value = track.sample(i as f64 / 1000.0);
+                assert!(
+                    min <= value && value <= max,
+                    "{} outside [{}, {}]",
+                    value,
+                    min,
+                    max
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::f64::consts::PI;
+
+use crate::{
+    distribution::Distribution2D,
+    image::{color::Color, ExtImage, WrapMode},
+    random::gen_f64,
+    vec3::Vec3,
+};
+
+/// Equirectangular environment map, with +y at the top row and the same longitude as sphere uvs
+pub struct Environment {
+    image: ExtImage,
+    // Sampling density over the image, from the luminance of its texels
+    distribution: Distribution2D,
+    // Radians around +y
+    rotation: f64,
+    intensity: f64,
+}
+
+impl Environment {
+    pub fn new(image: ExtImage) -> Self {
+        let (width, height) = (image.width(), image.height());
+        let mut function = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            // Rows near the poles cover less solid angle
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+
+            for x in 0..width {
+                let [r, g, b, _] = image.texel(0, x as i64, y as i64);
+                let color = Color::new(r as f64, g as f64, b as f64);
+
+                function.push(color.luminance() * sin_theta);
+            }
+        }
+
+        Self {
+            image: image.with_wrap_uv(WrapMode::Repeat, WrapMode::Clamp),
+            distribution: Distribution2D::new(&function, width, height),
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+
+    pub fn load(file_name: &str) -> Result<Self, String> {
+        Ok(Self::new(ExtImage::load(file_name)?))
+    }
+
+    /// Turns the environment around +y, in degrees
+    pub fn with_rotation(mut self, rotation: f64) -> Self {
+        self.rotation = rotation.to_radians();
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn value(&self, direction: &Vec3) -> Color {
+        let (x, y) = self.to_image(direction);
+        let [r, g, b, _] = self.image.sample((x, 1.0 - y));
+
+        self.intensity * Color::new(r, g, b)
+    }
+
+    pub fn sample(&self) -> Vec3 {
+        let ((x, y), _) = self.distribution.sample((gen_f64(), gen_f64()));
+
+        self.to_direction(x, y)
+    }
+
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let (x, y) = self.to_image(direction);
+        let sin_theta = (PI * y).sin();
+
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        // Change of variables from the image square to solid angle
+        self.distribution.pdf((x, y)) / (2.0 * PI * PI * sin_theta)
+    }
+
+    /// Image coordinates in [0, 1)², y pointing down from +y
+    fn to_image(&self, direction: &Vec3) -> (f64, f64) {
+        let direction = rotate_y(&direction.normalize(), -self.rotation);
+
+        let x = direction.z.atan2(-direction.x).rem_euclid(2.0 * PI) / (2.0 * PI);
+        let y = direction.y.clamp(-1.0, 1.0).acos() / PI;
+
+        (x, y)
+    }
+
+    fn to_direction(&self, x: f64, y: f64) -> Vec3 {
+        let (sin_theta, cos_theta) = (PI * y).sin_cos();
+        let (sin_phi, cos_phi) = (2.0 * PI * x).sin_cos();
+
+        let direction = Vec3::new(-sin_theta * cos_phi, cos_theta, sin_theta * sin_phi);
+
+        rotate_y(&direction, self.rotation)
+    }
+}
+
+fn rotate_y(v: &Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+
+    Vec3::new(cos * v.x + sin * v.z, v.y, -sin * v.x + cos * v.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small panorama with a bright patch, so sampling is far from uniform
+    fn environment() -> Environment {
+        let (width, height) = (8, 4);
+        let texels = (0..width * height)
+            .map(|i| {
+                let value = if i == 10 { 20.0 } else { 0.1 + (i % 3) as f32 };
+                [value, value, value, 1.0]
+            })
+            .collect();
+
+        Environment::new(ExtImage::from_texels(width, height, texels))
+    }
+
+    #[test]
+    fn pdf_integrates_to_one_over_the_sphere() {
+        let (theta_steps, phi_steps) = (400, 800);
+        let d_theta = PI / theta_steps as f64;
+        let d_phi = 2.0 * PI / phi_steps as f64;
+
+        for environment in [environment(), environment().with_rotation(70.0)] {
+            let mut integral = 0.0;
+
+            for i in 0..theta_steps {
+                let (sin_theta, cos_theta) = ((i as f64 + 0.5) * d_theta).sin_cos();
+
+                for j in 0..phi_steps {
+                    let (sin_phi, cos_phi) = ((j as f64 + 0.5) * d_phi).sin_cos();
+                    let direction = Vec3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi);
+
+                    integral += environment.pdf(&direction) * sin_theta * d_theta * d_phi;
+                }
+            }
+
+            assert!((integral - 1.0).abs() < 1e-3, "{}", integral);
+        }
+    }
+
+    #[test]
+    fn sampled_directions_have_the_solid_angle_pdf() {
+        let n = 50;
+
+        for environment in [environment(), environment().with_rotation(-35.0)] {
+            for i in 0..n {
+                for j in 0..n {
+                    let u = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+                    let ((x, y), pdf) = environment.distribution.sample(u);
+                    let direction = environment.to_direction(x, y);
+
+                    // Image square to sphere, 2π in longitude times π in latitude times sin θ
+                    let expected = pdf / (2.0 * PI * PI * (PI * y).sin());
+                    let actual = environment.pdf(&direction);
+
+                    assert!(actual > 0.0);
+                    assert!((actual / expected - 1.0).abs() < 1e-9, "{}", actual);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn image_coordinates_round_trip() {
+        let environment = environment().with_rotation(120.0);
+
+        for (x, y) in [(0.1, 0.2), (0.5, 0.5), (0.95, 0.8)] {
+            let (u, v) = environment.to_image(&environment.to_direction(x, y));
+
+            assert!((u - x).abs() < 1e-9 && (v - y).abs() < 1e-9);
+        }
+    }
+}
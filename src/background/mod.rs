@@ -0,0 +1,71 @@
+pub mod environment;
+pub mod sky;
+
+use std::sync::Arc;
+
+use crate::{image::color::Color, vec3::Vec3};
+
+use environment::Environment;
+use sky::Sky;
+
+/// Radiance seen by rays leaving the scene
+///
+/// Environments and skies are importance sampled from diffuse materials only (see `Material::is_diffuse`),
+/// bounces off `Principled`, `Coated` and other specular materials find bright spots by chance.
+#[derive(Clone)]
+pub enum Background {
+    Color(Color),
+    Environment(Arc<Environment>),
+    Sky(Arc<Sky>),
+}
+
+impl From<Color> for Background {
+    fn from(color: Color) -> Self {
+        Self::Color(color)
+    }
+}
+
+impl From<Environment> for Background {
+    fn from(environment: Environment) -> Self {
+        Self::Environment(Arc::new(environment))
+    }
+}
+
+impl From<Sky> for Background {
+    fn from(sky: Sky) -> Self {
+        Self::Sky(Arc::new(sky))
+    }
+}
+
+impl Background {
+    pub fn value(&self, direction: &Vec3) -> Color {
+        match self {
+            Background::Color(color) => *color,
+            Background::Environment(environment) => environment.value(direction),
+            Background::Sky(sky) => sky.value(direction),
+        }
+    }
+
+    /// Whether bounces should also sample directions from `sample`
+    pub fn is_importance_sampled(&self) -> bool {
+        matches!(self, Background::Environment(_) | Background::Sky(_))
+    }
+
+    /// Direction towards the background chosen proportionally to its brightness
+    pub fn sample(&self) -> Option<Vec3> {
+        match self {
+            Background::Color(_) => None,
+            Background::Environment(environment) => Some(environment.sample()),
+            Background::Sky(sky) => Some(sky.sample()),
+        }
+    }
+
+    /// Solid angle density of `sample` choosing `direction`
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        match self {
+            Background::Color(_) => 0.0,
+            Background::Environment(environment) => environment.pdf(direction),
+            Background::Sky(sky) => sky.pdf(direction),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::f64::consts::PI;
+
+use crate::{
+    image::color::Color, material::light::LightUnit, onb::OrthonormalBasis, random::gen_f64,
+    spectrum, vec3::Vec3,
+};
+
+/// Illuminance of the sun above the atmosphere, in lux
+const SOLAR_ILLUMINANCE: f64 = 128_000.0;
+
+/// Preetham et al. 1999, "A Practical Analytic Model for Daylight", with a matching sun disk
+///
+/// +y is up, north is -z and east is +x. Radiance uses the same units as `LightUnit`.
+#[derive(Debug, Clone)]
+pub struct Sky {
+    sun_direction: Vec3,
+    // Haziness, 2 for a clear sky up to around 10 for a hazy one
+    turbidity: f64,
+    ground_albedo: Color,
+    // Angular radius of the sun disk in radians
+    sun_radius: f64,
+    intensity: f64,
+    // Location for `at_hour`
+    latitude: f64,
+    day_of_year: f64,
+    // Derived from the parameters above by `update`
+    perez: [[f64; 5]; 3],
+    zenith: [f64; 3],
+    sun_radiance: Color,
+    ground_radiance: Color,
+}
+
+impl Sky {
+    pub fn new(sun_direction: Vec3, turbidity: f64, ground_albedo: Color) -> Self {
+        let mut sky = Self {
+            sun_direction: sun_direction.normalize(),
+            turbidity: turbidity.max(1.0),
+            ground_albedo,
+            sun_radius: 0.2667_f64.to_radians(),
+            intensity: 1.0,
+            latitude: 45.0,
+            day_of_year: 172.0,
+            perez: [[0.0; 5]; 3],
+            zenith: [0.0; 3],
+            sun_radiance: Color::black(),
+            ground_radiance: Color::black(),
+        };
+        sky.update();
+
+        sky
+    }
+
+    pub fn with_sun_direction(mut self, sun_direction: Vec3) -> Self {
+        self.sun_direction = sun_direction.normalize();
+        self.update();
+        self
+    }
+
+    /// Angular radius of the sun in degrees, its total light stays the same
+    pub fn with_sun_radius(mut self, sun_radius: f64) -> Self {
+        self.sun_radius = sun_radius.max(1e-3).to_radians();
+        self.update();
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Latitude in degrees and day of the year used by `at_hour`
+    pub fn with_location(mut self, latitude: f64, day_of_year: f64) -> Self {
+        self.latitude = latitude;
+        self.day_of_year = day_of_year;
+        self
+    }
+
+    /// The same sky with the sun placed for a local solar time in hours, e.g. 13.5
+    pub fn at_hour(&self, hour: f64) -> Self {
+        self.clone()
+            .with_sun_direction(sun_direction(hour, self.day_of_year, self.latitude))
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    pub fn value(&self, direction: &Vec3) -> Color {
+        let direction = direction.normalize();
+
+        if direction.y < 0.0 {
+            return self.intensity * self.ground_radiance;
+        }
+
+        let mut radiance = self.sky_radiance(&direction);
+        if direction.dot(&self.sun_direction) >= self.sun_radius.cos() {
+            radiance += self.sun_radiance;
+        }
+
+        self.intensity * radiance
+    }
+
+    /// Picks the sun disk or a uniform direction with equal chance
+    pub fn sample(&self) -> Vec3 {
+        if gen_f64() < 0.5 {
+            let cos_theta = 1.0 - gen_f64() * (1.0 - self.sun_radius.cos());
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let (sin_phi, cos_phi) = (2.0 * PI * gen_f64()).sin_cos();
+
+            OrthonormalBasis::from_w(&self.sun_direction).to_world(&Vec3::new(
+                sin_theta * cos_phi,
+                sin_theta * sin_phi,
+                cos_theta,
+            ))
+        } else {
+            Vec3::random_unit()
+        }
+    }
+
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let sun = if direction.normalize().dot(&self.sun_direction) >= self.sun_radius.cos() {
+            1.0 / self.sun_solid_angle()
+        } else {
+            0.0
+        };
+
+        0.5 * sun + 0.5 / (4.0 * PI)
+    }
+
+    fn sun_solid_angle(&self) -> f64 {
+        2.0 * PI * (1.0 - self.sun_radius.cos())
+    }
+
+    /// Recomputes the model's coefficients after a parameter changed
+    fn update(&mut self) {
+        let t = self.turbidity;
+
+        // The model only covers the sun above the horizon, twilight fades the sky out below it
+        let elevation = self.sun_direction.y.clamp(-1.0, 1.0).asin();
+        let theta_s = (PI / 2.0 - elevation).min(PI / 2.0);
+        let twilight = (1.0 + elevation / 6.0_f64.to_radians()).clamp(0.0, 1.0);
+
+        self.perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        // Zenith luminance in kcd/m² and chromaticity
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+
+        let cubic = |c: [f64; 4]| ((c[0] * theta_s + c[1]) * theta_s + c[2]) * theta_s + c[3];
+        let x = t * t * cubic([0.00166, -0.00375, 0.00209, 0.0])
+            + t * cubic([-0.02903, 0.06377, -0.03202, 0.00394])
+            + cubic([0.11693, -0.21196, 0.06052, 0.25886]);
+        let y = t * t * cubic([0.00275, -0.00610, 0.00317, 0.0])
+            + t * cubic([-0.04214, 0.08970, -0.04153, 0.00516])
+            + cubic([0.15346, -0.26756, 0.06670, 0.26688]);
+
+        let radiance = LightUnit::Nits(1000.0 * luminance * twilight).radiance(1.0);
+        self.zenith = [radiance, x, y];
+
+        self.sun_radiance = self.compute_sun_radiance(theta_s, elevation);
+        self.ground_radiance = self.compute_ground_radiance();
+    }
+
+    fn sky_radiance(&self, direction: &Vec3) -> Color {
+        // Clamped to avoid dividing by zero at the horizon
+        let cos_theta = direction.y.max(1e-3);
+        let cos_gamma = direction.dot(&self.sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+
+        let cos_theta_s = self.sun_direction.y.max(0.0);
+        let theta_s = cos_theta_s.acos();
+
+        let perez = |[a, b, c, d, e]: [f64; 5], cos_theta: f64, gamma: f64| {
+            (1.0 + a * (b / cos_theta).exp())
+                * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+        };
+        let relative = |i: usize| {
+            self.zenith[i] * perez(self.perez[i], cos_theta, gamma)
+                / perez(self.perez[i], 1.0, theta_s)
+        };
+
+        let (luminance, x, y) = (relative(0), relative(1), relative(2));
+        if y <= 0.0 {
+            return Color::black();
+        }
+
+        let rgb = spectrum::xyz_to_rgb(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+
+        Color::new(rgb.r.max(0.0), rgb.g.max(0.0), rgb.b.max(0.0))
+    }
+
+    /// Sunlight after Rayleigh and aerosol extinction along the air mass (Preetham et al. appendix)
+    fn compute_sun_radiance(&self, theta_s: f64, elevation: f64) -> Color {
+        if elevation < -self.sun_radius {
+            return Color::black();
+        }
+
+        // Relative optical air mass (Kasten 1966)
+        let theta_degrees = theta_s.to_degrees();
+        let air_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_degrees).powf(-1.253));
+
+        // Ångström's turbidity formula for the aerosols
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |lambda: f64| {
+            let rayleigh = -0.008735 * lambda.powf(-4.08) * air_mass;
+            let aerosol = -beta * lambda.powf(-1.3) * air_mass;
+
+            (rayleigh + aerosol).exp()
+        };
+
+        // Wavelengths in micrometres for each channel
+        let color = spectrum::blackbody_color(5778.0);
+        let radiance = LightUnit::Nits(SOLAR_ILLUMINANCE / self.sun_solid_angle()).radiance(1.0);
+
+        radiance
+            * Color::new(
+                color.r * transmittance(0.65),
+                color.g * transmittance(0.55),
+                color.b * transmittance(0.45),
+            )
+    }
+
+    /// Lambertian ground lit by the sky and sun, ignoring anything standing on it
+    fn compute_ground_radiance(&self) -> Color {
+        const THETA_STEPS: usize = 16;
+        const PHI_STEPS: usize = 32;
+
+        let mut irradiance = Color::black();
+        let d_theta = 0.5 * PI / THETA_STEPS as f64;
+        let d_phi = 2.0 * PI / PHI_STEPS as f64;
+
+        for i in 0..THETA_STEPS {
+            let theta = (i as f64 + 0.5) * d_theta;
+            let (sin_theta, cos_theta) = theta.sin_cos();
+
+            for j in 0..PHI_STEPS {
+                let (sin_phi, cos_phi) = ((j as f64 + 0.5) * d_phi).sin_cos();
+                let direction = Vec3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi);
+
+                irradiance +=
+                    self.sky_radiance(&direction) * (cos_theta * sin_theta * d_theta * d_phi);
+            }
+        }
+
+        let sun_cos = self.sun_direction.y.max(0.0);
+        irradiance += self.sun_radiance * (self.sun_solid_angle() * sun_cos);
+
+        self.ground_albedo * irradiance * (1.0 / PI)
+    }
+}
+
+/// Direction of the sun for a local solar time in hours, day of the year and latitude in degrees
+pub fn sun_direction(hour: f64, day_of_year: f64, latitude: f64) -> Vec3 {
+    let declination = (23.44_f64).to_radians() * (2.0 * PI * (284.0 + day_of_year) / 365.0).sin();
+    let hour_angle = (15.0 * (hour - 12.0)).to_radians();
+    let latitude = latitude.to_radians();
+
+    // East, north and up components of the direction
+    let east = -declination.cos() * hour_angle.sin();
+    let north =
+        latitude.cos() * declination.sin() - latitude.sin() * declination.cos() * hour_angle.cos();
+    let up =
+        latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+
+    Vec3::new(east, up, -north).normalize()
+}
@@ -1,98 +1,331 @@
 use std::{
-    io::{self, Write},
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
-    },
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
 };
 
 use nanorand::{Rng, WyRand};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 use crate::{
-    hittable::Hittable,
+    background::Background,
+    cancellation::RenderLimits,
+    hittable::{HitRecord, Hittable},
     image::{color::Color, Image},
     interval::Interval,
-    ray::Ray,
+    lens::LensSystem,
+    progress::{ProgressSink, RenderProgress},
+    random::gen_f64,
+    ray::{Ray, RayDifferentials},
+    spectrum::{self, SampledSpectrum, SampledWavelengths},
     vec3::Vec3,
 };
 
+/// Image from a possibly stopped render, with the chunks that were finished
+pub struct RenderOutput {
+    pub image: Image,
+    pub completed: CompletionMap,
+}
+
+/// Grid of square chunks covering the image, marking which ones were rendered
+#[derive(Debug, Clone)]
+pub struct CompletionMap {
+    chunk_size: usize,
+    chunks_x: usize,
+    chunks_y: usize,
+    completed: Vec<bool>,
+}
+
+impl CompletionMap {
+    pub fn is_complete(&self) -> bool {
+        self.completed.iter().all(|&done| done)
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn chunks(&self) -> (usize, usize) {
+        (self.chunks_x, self.chunks_y)
+    }
+
+    pub fn is_chunk_done(&self, chunk_x: usize, chunk_y: usize) -> bool {
+        self.completed[chunk_y * self.chunks_x + chunk_x]
+    }
+
+    pub fn is_pixel_done(&self, row: usize, col: usize) -> bool {
+        self.is_chunk_done(col / self.chunk_size, row / self.chunk_size)
+    }
+
+    /// Pixel bounds `(start_x, start_y, end_x, end_y)` of every finished chunk, clipped to the image
+    pub fn completed_regions(&self, image_width: usize, image_height: usize) -> Vec<[usize; 4]> {
+        let mut regions = Vec::new();
+
+        for chunk_y in 0..self.chunks_y {
+            for chunk_x in 0..self.chunks_x {
+                if self.is_chunk_done(chunk_x, chunk_y) {
+                    let start_x = chunk_x * self.chunk_size;
+                    let start_y = chunk_y * self.chunk_size;
+
+                    regions.push([
+                        start_x,
+                        start_y,
+                        (start_x + self.chunk_size).min(image_width),
+                        (start_y + self.chunk_size).min(image_height),
+                    ]);
+                }
+            }
+        }
+
+        regions
+    }
+}
+
 pub struct Camera {
-    pub aspect_ratio: f64,
-    pub image_width: usize,
+    aspect_ratio: f64,
+    image_width: usize,
     image_height: usize,
-    pub vfov: f64,
-    pub center: Vec3,
-    pub look_at: Vec3,
-    pub v_up: Vec3,
-    pub defocus_angle: f64,
-    pub focus_dist: f64,
+    vfov: f64,
+    center: Vec3,
+    look_at: Vec3,
+    v_up: Vec3,
+    defocus_angle: f64,
+    focus_dist: f64,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
     top_left_pixel_pos: Vec3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
-    background: Color,
-    pub samples_per_pixel: usize,
-    pub pixel_samples_scale: f64,
-    pub max_depth: usize,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    lens: Option<LensSystem>,
+    film_diagonal: f64,
+    film_width: f64,
+    film_height: f64,
+    background: Background,
+    samples_per_pixel: usize,
+    pixel_samples_scale: f64,
+    max_depth: usize,
+    shutter_open: f64,
+    shutter_close: f64,
+    spectral: bool,
 }
 
-impl Camera {
-    pub fn default() -> Self {
+#[derive(Clone)]
+pub struct CameraBuilder {
+    aspect_ratio: f64,
+    image_width: usize,
+    vfov: f64,
+    look_from: Vec3,
+    look_at: Vec3,
+    v_up: Vec3,
+    defocus_angle: f64,
+    focus_dist: f64,
+    lens: Option<(LensSystem, f64)>,
+    background: Background,
+    samples_per_pixel: usize,
+    max_depth: usize,
+    shutter_open: f64,
+    shutter_close: f64,
+    spectral: bool,
+}
+
+impl Default for CameraBuilder {
+    fn default() -> Self {
         Self {
             aspect_ratio: 1.0,
             image_width: 100,
-            image_height: 0,
             vfov: 90.0,
-            center: Vec3::zero(),
+            look_from: Vec3::zero(),
             look_at: Vec3::new(0.0, 0.0, -1.0),
             v_up: Vec3::up(),
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            lens: None,
+            background: Background::Color(Color::black()),
+            samples_per_pixel: 10,
+            max_depth: 10,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            spectral: false,
+        }
+    }
+}
+
+impl CameraBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn aspect_ratio(mut self, aspect_ratio: f64) -> Self {
+        self.aspect_ratio = aspect_ratio;
+        self
+    }
+
+    pub fn image_width(mut self, image_width: usize) -> Self {
+        self.image_width = image_width;
+        self
+    }
+
+    /// Vertical field of view in degrees
+    pub fn vfov(mut self, vfov: f64) -> Self {
+        self.vfov = vfov;
+        self
+    }
+
+    pub fn look_from(mut self, look_from: Vec3) -> Self {
+        self.look_from = look_from;
+        self
+    }
+
+    pub fn look_at(mut self, look_at: Vec3) -> Self {
+        self.look_at = look_at;
+        self
+    }
+
+    pub fn v_up(mut self, v_up: Vec3) -> Self {
+        self.v_up = v_up;
+        self
+    }
+
+    /// Variation angle of rays through each pixel in degrees
+    pub fn defocus_angle(mut self, defocus_angle: f64) -> Self {
+        self.defocus_angle = defocus_angle;
+        self
+    }
+
+    pub fn focus_dist(mut self, focus_dist: f64) -> Self {
+        self.focus_dist = focus_dist;
+        self
+    }
+
+    /// Traces camera rays through a lens prescription instead of using a pinhole/thin lens
+    ///
+    /// The lens is focused at `focus_dist`, and `film_diagonal` (in millimetres) replaces `vfov` and `defocus_angle`
+    pub fn lens(mut self, lens: LensSystem, film_diagonal: f64) -> Self {
+        self.lens = Some((lens, film_diagonal));
+        self
+    }
+
+    /// A colour or an environment, see `Background`
+    pub fn background(mut self, background: impl Into<Background>) -> Self {
+        self.background = background.into();
+        self
+    }
+
+    pub fn samples_per_pixel(mut self, samples_per_pixel: usize) -> Self {
+        self.samples_per_pixel = samples_per_pixel;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Time interval (in seconds) that ray times are sampled from, for motion blur
+    pub fn shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    /// Traces sampled spectra instead of RGB, with colours upsampled at every bounce
+    pub fn spectral(mut self, spectral: bool) -> Self {
+        self.spectral = spectral;
+        self
+    }
+
+    pub fn build(self) -> Result<Camera, String> {
+        if self.image_width == 0 {
+            return Err("Image width must be greater than 0".to_string());
+        }
+        if !(self.aspect_ratio > 0.0 && self.aspect_ratio.is_finite()) {
+            return Err(format!("Invalid aspect ratio {}", self.aspect_ratio));
+        }
+        if !(self.vfov > 0.0 && self.vfov < 180.0) {
+            return Err(format!("Vertical fov {} is not in (0, 180)", self.vfov));
+        }
+        if self.shutter_close < self.shutter_open {
+            return Err(format!(
+                "Shutter closes at {} before it opens at {}",
+                self.shutter_close, self.shutter_open
+            ));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err("Samples per pixel must be greater than 0".to_string());
+        }
+        if !self.focus_dist.is_finite() || self.focus_dist <= 0.0 {
+            return Err(format!(
+                "Focus distance {} must be positive",
+                self.focus_dist
+            ));
+        }
+
+        let view_direction = self.look_at - self.look_from;
+        if view_direction.near_zero() {
+            return Err("Look from and look at are the same point".to_string());
+        }
+        if self.v_up.cross(&view_direction).near_zero() {
+            return Err("View up is parallel to the view direction".to_string());
+        }
+
+        let (lens, film_diagonal) = match self.lens {
+            Some((lens, film_diagonal)) => (Some(lens), film_diagonal),
+            None => (None, 0.0),
+        };
+
+        let mut camera = Camera {
+            aspect_ratio: self.aspect_ratio,
+            image_width: self.image_width,
+            image_height: 0,
+            vfov: self.vfov,
+            center: self.look_from,
+            look_at: self.look_at,
+            v_up: self.v_up,
+            defocus_angle: self.defocus_angle,
+            focus_dist: self.focus_dist,
             defocus_disk_u: Vec3::zero(),
             defocus_disk_v: Vec3::zero(),
             top_left_pixel_pos: Vec3::zero(),
             pixel_delta_u: Vec3::zero(),
             pixel_delta_v: Vec3::zero(),
-            background: Color::black(),
-            samples_per_pixel: 10,
+            u: Vec3::zero(),
+            v: Vec3::zero(),
+            w: Vec3::zero(),
+            lens,
+            film_diagonal,
+            film_width: 0.0,
+            film_height: 0.0,
+            background: self.background,
+            samples_per_pixel: self.samples_per_pixel,
             pixel_samples_scale: 0.0,
-            max_depth: 10,
-        }
+            max_depth: self.max_depth,
+            shutter_open: self.shutter_open,
+            shutter_close: self.shutter_close,
+            spectral: self.spectral,
+        };
+
+        camera.initialize()?;
+
+        Ok(camera)
+    }
+}
+
+impl Camera {
+    pub fn builder() -> CameraBuilder {
+        CameraBuilder::new()
+    }
+
+    pub fn image_width(&self) -> usize {
+        self.image_width
     }
 
-    pub fn new(
-        aspect_ratio: f64,
-        image_width: usize,
-        vfov: f64,
-        look_from: Vec3,
-        look_at: Vec3,
-        defocus_angle: f64,
-        focus_dist: f64,
-        background: Color,
-        samples_per_pixel: usize,
-        max_depth: usize,
-    ) -> Self {
-        let mut camera = Self::default();
-
-        camera.aspect_ratio = aspect_ratio;
-        camera.image_width = image_width;
-        camera.vfov = vfov;
-        camera.center = look_from;
-        camera.look_at = look_at;
-        camera.defocus_angle = defocus_angle;
-        camera.focus_dist = focus_dist;
-        camera.background = background;
-        camera.samples_per_pixel = samples_per_pixel;
-        camera.max_depth = max_depth;
-
-        camera.initialize();
-
-        camera
-    }
-
-    fn initialize(&mut self) {
+    pub fn image_height(&self) -> usize {
+        self.image_height
+    }
+
+    fn initialize(&mut self) -> Result<(), String> {
         self.image_height = ((self.image_width as f64 / self.aspect_ratio) as usize).max(1);
 
         self.pixel_samples_scale = 1.0 / self.samples_per_pixel as f64;
@@ -107,6 +340,10 @@ impl Camera {
         let u = self.v_up.cross(&w).normalize();
         let v = w.cross(&u);
 
+        self.u = u;
+        self.v = v;
+        self.w = w;
+
         let viewport_u = viewport_width * u;
         let viewport_v = viewport_height * -v;
 
@@ -122,34 +359,70 @@ impl Camera {
         let defocus_radius = self.focus_dist * (self.defocus_angle / 2.0).to_radians().tan();
         self.defocus_disk_u = u * defocus_radius;
         self.defocus_disk_v = v * defocus_radius;
+
+        if let Some(lens) = &mut self.lens {
+            lens.focus(self.focus_dist)?;
+
+            let film_diagonal = self.film_diagonal * 0.001;
+            let aspect = self.image_width as f64 / self.image_height as f64;
+
+            self.film_height = film_diagonal / (1.0 + aspect * aspect).sqrt();
+            self.film_width = aspect * self.film_height;
+        }
+
+        Ok(())
     }
 
-    pub fn render_single_threaded(&self, world: &impl Hittable) -> Image {
+    pub fn render_single_threaded(
+        &self,
+        world: &impl Hittable,
+        progress: &dyn ProgressSink,
+    ) -> Image {
         let mut image = Image::new(self.image_width, self.image_height);
 
         let mut rng = WyRand::new();
 
-        for row in 0..self.image_height {
-            print!("\rScanlines remaining: {} ", self.image_height - row);
-            io::stdout().flush().unwrap();
+        // Each scanline counts as a chunk
+        let start_time = Instant::now();
+        let mut report = RenderProgress {
+            chunks_done: 0,
+            total_chunks: self.image_height,
+            samples_done: 0,
+            elapsed: Duration::ZERO,
+        };
+        progress.started(&report);
 
+        for row in 0..self.image_height {
             for col in 0..self.image_width {
                 let mut color = Color::black();
 
                 for _ in 0..self.samples_per_pixel {
-                    let ray = self.get_ray(col, row, &mut rng);
-                    color += self.ray_color(ray, self.max_depth, world);
+                    if let Some(ray) = self.get_ray(col, row, &mut rng) {
+                        color += self.ray_color(ray, self.max_depth, world);
+                    }
                 }
 
                 image.set_pixel(color * self.pixel_samples_scale, row, col);
             }
+
+            report.chunks_done += 1;
+            report.samples_done += self.image_width * self.samples_per_pixel;
+            report.elapsed = start_time.elapsed();
+            progress.chunk_done(&report);
         }
-        println!("\rDone!                             ");
+
+        progress.finished(&report);
 
         image
     }
 
-    pub fn render<H: Hittable + Sync>(&self, world: &H) -> Image {
+    /// Renders in parallel chunks until done or until `limits` stop new chunks from being scheduled
+    pub fn render<H: Hittable + Sync>(
+        &self,
+        world: &H,
+        progress: &dyn ProgressSink,
+        limits: &RenderLimits,
+    ) -> RenderOutput {
         let mut image = Image::new(self.image_width, self.image_height);
 
         // Define square chunk dimensions
@@ -160,13 +433,16 @@ impl Camera {
         let chunks_y = (self.image_height + chunk_size - 1) / chunk_size;
         let total_chunks = chunks_x * chunks_y;
 
-        let chunks_remaining = Arc::new(AtomicUsize::new(total_chunks));
+        let chunks_done = AtomicUsize::new(0);
+        let samples_done = AtomicUsize::new(0);
+        let start_time = Instant::now();
 
-        // Initial progress report
-        println!(
-            "Starting render: {} chunks to process ({} x {})",
-            total_chunks, chunks_x, chunks_y
-        );
+        progress.started(&RenderProgress {
+            chunks_done: 0,
+            total_chunks,
+            samples_done: 0,
+            elapsed: Duration::ZERO,
+        });
 
         // Create a collection of all chunk coordinates
         let chunk_coordinates: Vec<(usize, usize)> = (0..chunks_y)
@@ -176,7 +452,12 @@ impl Camera {
         // Process chunks in parallel and collect the results
         let chunk_results: Vec<_> = chunk_coordinates
             .into_par_iter()
-            .map(|(chunk_x, chunk_y)| {
+            .filter_map(|(chunk_x, chunk_y)| {
+                // Chunks already in flight finish, but no new ones are started
+                if limits.should_stop(start_time) {
+                    return None;
+                }
+
                 let mut rng = WyRand::new();
                 let mut local_buffer = Vec::with_capacity(chunk_size * chunk_size);
 
@@ -192,8 +473,9 @@ impl Camera {
                         let mut pixel_color = Color::black();
 
                         for _ in 0..self.samples_per_pixel {
-                            let ray = self.get_ray(col, row, &mut rng);
-                            pixel_color += self.ray_color(ray, self.max_depth, world);
+                            if let Some(ray) = self.get_ray(col, row, &mut rng) {
+                                pixel_color += self.ray_color(ray, self.max_depth, world);
+                            }
                         }
 
                         local_buffer.push((row, col, pixel_color * self.pixel_samples_scale));
@@ -201,31 +483,54 @@ impl Camera {
                 }
 
                 // Update progress after each chunk is processed
-                let remaining = chunks_remaining.fetch_sub(1, Ordering::Relaxed) - 1;
-                print!("\rChunks remaining: {}/{} ", remaining, total_chunks);
-                if let Err(e) = io::stdout().flush() {
-                    eprintln!("Warning: Could not flush stdout: {}", e);
-                }
-
-                local_buffer
+                let chunk_samples = local_buffer.len() * self.samples_per_pixel;
+                progress.chunk_done(&RenderProgress {
+                    chunks_done: chunks_done.fetch_add(1, Ordering::Relaxed) + 1,
+                    total_chunks,
+                    samples_done: samples_done.fetch_add(chunk_samples, Ordering::Relaxed)
+                        + chunk_samples,
+                    elapsed: start_time.elapsed(),
+                });
+
+                Some((chunk_y * chunks_x + chunk_x, local_buffer))
             })
             .collect();
 
+        let mut completed = CompletionMap {
+            chunk_size,
+            chunks_x,
+            chunks_y,
+            completed: vec![false; total_chunks],
+        };
+
         // Set the pixels of the image
-        for chunk_result in chunk_results {
+        for (chunk_index, chunk_result) in chunk_results {
+            completed.completed[chunk_index] = true;
+
             for (row, col, color) in chunk_result {
                 image.set_pixel(color, row, col);
             }
         }
 
-        println!("\rDone!                                          ");
-        image
+        progress.finished(&RenderProgress {
+            chunks_done: chunks_done.into_inner(),
+            total_chunks,
+            samples_done: samples_done.into_inner(),
+            elapsed: start_time.elapsed(),
+        });
+
+        RenderOutput { image, completed }
     }
 
-    fn get_ray(&self, col: usize, row: usize, rng: &mut WyRand) -> Ray {
+    fn get_ray(&self, col: usize, row: usize, rng: &mut WyRand) -> Option<Ray> {
         // Construct a camera ray from origin and directed at randomly sampled point around pixel location i, j
 
         let offset = self.sample_square(rng);
+
+        if let Some(lens) = &self.lens {
+            return self.get_lens_ray(lens, col as f64 + offset.x, row as f64 + offset.y, rng);
+        }
+
         let pixel_sample_pos = self.top_left_pixel_pos
             + (col as f64 + offset.x) * self.pixel_delta_u
             + (row as f64 + offset.y) * self.pixel_delta_v;
@@ -237,9 +542,60 @@ impl Camera {
         };
 
         let ray_direction = pixel_sample_pos - ray_origin;
-        let ray_time = rng.generate();
+        let ray_time = self.sample_time(rng);
+
+        // Neighbouring pixels through the same point on the lens
+        let differentials = RayDifferentials {
+            rx_origin: ray_origin,
+            rx_direction: ray_direction + self.pixel_delta_u,
+            ry_origin: ray_origin,
+            ry_direction: ray_direction + self.pixel_delta_v,
+        };
 
-        Ray::new(ray_origin, ray_direction, ray_time)
+        Some(Ray::new(ray_origin, ray_direction, ray_time).with_differentials(Some(differentials)))
+    }
+
+    /// Traces a ray from the film through the lens system, `None` if it is blocked inside the lens
+    fn get_lens_ray(&self, lens: &LensSystem, x: f64, y: f64, rng: &mut WyRand) -> Option<Ray> {
+        let rear_pos = lens.rear_aperture_radius() * Vec3::random_in_unit_disk()
+            + Vec3::new(0.0, 0.0, -lens.rear_z());
+        let time = self.sample_time(rng);
+
+        let to_world = |p: Vec3| p.x * self.u + p.y * self.v + p.z * self.w;
+        let trace = |x: f64, y: f64| {
+            // The lens flips the image, so the top left pixel sits at the bottom right of the film
+            let film_x = (0.5 - x / self.image_width as f64) * self.film_width;
+            let film_y = (y / self.image_height as f64 - 0.5) * self.film_height;
+            let film_pos = Vec3::new(film_x, film_y, 0.0);
+
+            let scene_ray = lens.trace_from_film(&Ray::new(film_pos, rear_pos - film_pos, time))?;
+
+            Some((
+                self.center + to_world(scene_ray.origin),
+                to_world(scene_ray.direction),
+            ))
+        };
+
+        let (origin, direction) = trace(x, y)?;
+
+        // Neighbouring pixels through the same point on the rear element, dropped if the lens blocks them
+        let differentials = match (trace(x + 1.0, y), trace(x, y + 1.0)) {
+            (Some((rx_origin, rx_direction)), Some((ry_origin, ry_direction))) => {
+                Some(RayDifferentials {
+                    rx_origin,
+                    rx_direction,
+                    ry_origin,
+                    ry_direction,
+                })
+            }
+            _ => None,
+        };
+
+        Some(Ray::new(origin, direction, time).with_differentials(differentials))
+    }
+
+    fn sample_time(&self, rng: &mut WyRand) -> f64 {
+        self.shutter_open + rng.generate::<f64>() * (self.shutter_close - self.shutter_open)
     }
 
     fn sample_square(&self, rng: &mut WyRand) -> Vec3 {
@@ -257,35 +613,172 @@ impl Camera {
     }
 
     fn ray_color(&self, ray: Ray, depth: usize, world: &impl Hittable) -> Color {
+        if self.spectral {
+            return self.spectral_ray_color(ray, depth, world);
+        }
+
         let mut ray = ray;
         let mut color = Color::black();
         let mut attenuation_accumulator = Color::new(1.0, 1.0, 1.0);
 
         for _ in 0..depth {
-            if let Some(hit_record) = world.hit(&ray, Interval::new(0.001, f64::INFINITY)) {
-                let emission = hit_record.material.emitted(hit_record.uv, &hit_record.pos);
+            if let Some(mut hit_record) = world.hit(&ray, Interval::new(0.001, f64::INFINITY)) {
+                hit_record.compute_differentials(&ray);
+
+                let emission = hit_record.material.emitted(&hit_record);
                 // Emission is affected by all the attenuation up to this point
                 color = color + attenuation_accumulator * emission;
 
+                // The rest of the path is traced at a single wavelength
+                if ray.wavelength.is_none() && hit_record.material.is_dispersive() {
+                    let lambda = spectrum::sample_wavelength();
+                    attenuation_accumulator =
+                        attenuation_accumulator * spectrum::wavelength_weight(lambda);
+                    ray.wavelength = Some(lambda);
+                }
+
+                // Chromatic media are walked in one colour channel, unless a wavelength was already picked
+                if ray.channel.is_none()
+                    && ray.wavelength.is_none()
+                    && hit_record.material.is_chromatic()
+                {
+                    let channel = ((gen_f64() * 3.0) as usize).min(2);
+                    let mask = |n: usize| if n == channel { 3.0 } else { 0.0 };
+
+                    attenuation_accumulator =
+                        attenuation_accumulator * Color::new(mask(0), mask(1), mask(2));
+                    ray.channel = Some(channel);
+                }
+
                 if let Some((attenuation, scattered)) =
                     hit_record.material.scatter(&ray, &hit_record)
                 {
+                    let (attenuation, scattered) =
+                        self.sample_background(&ray, &hit_record, attenuation, scattered);
+
                     // Keep track of attenuation up to this point
                     attenuation_accumulator = attenuation_accumulator * attenuation;
 
                     // Set new ray
-                    ray = scattered;
+                    ray = scattered
+                        .with_wavelength(ray.wavelength)
+                        .with_channel(ray.channel);
                 } else {
                     // Hit light (purely emissive material)
                     break;
                 }
             } else {
                 // Hit nothing (Aka. hit background)
-                color = color + attenuation_accumulator * self.background;
+                color = color + attenuation_accumulator * self.background.value(&ray.direction);
                 break;
             }
         }
 
         color
     }
+
+    /// Sends half the bounces off diffuse materials towards the bright parts of the background
+    ///
+    /// The weight is f * cos over the mixture of the material's and background's pdfs.
+    fn sample_background(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        attenuation: Color,
+        scattered: Ray,
+    ) -> (Color, Ray) {
+        let material = &hit_record.material;
+
+        if !self.background.is_importance_sampled() || !material.is_diffuse() {
+            return (attenuation, scattered);
+        }
+
+        let scattered = match self.background.sample() {
+            Some(direction) if gen_f64() < 0.5 => {
+                Ray::new(hit_record.pos, direction, scattered.time)
+            }
+            _ => scattered,
+        };
+
+        // The material's own pdf uses its shading normal, which may differ from the hit's
+        let direction = scattered.direction.normalize();
+        let pdf = 0.5 * material.diffuse_pdf(ray, hit_record, &direction)
+            + 0.5 * self.background.pdf(&direction);
+
+        if pdf <= 0.0 {
+            return (Color::black(), scattered);
+        }
+
+        let weight = material.diffuse_eval(ray, hit_record, &direction) * (1.0 / pdf);
+
+        (weight, scattered)
+    }
+
+    fn spectral_ray_color(&self, ray: Ray, depth: usize, world: &impl Hittable) -> Color {
+        let wavelengths = SampledWavelengths::sample();
+        let upsample = |color: &Color| SampledSpectrum::from_rgb(color, &wavelengths);
+
+        let mut ray = ray;
+        let mut spectrum = SampledSpectrum::constant(0.0);
+        let mut throughput = SampledSpectrum::constant(1.0);
+
+        for _ in 0..depth {
+            if let Some(mut hit_record) = world.hit(&ray, Interval::new(0.001, f64::INFINITY)) {
+                hit_record.compute_differentials(&ray);
+
+                let emission = hit_record.material.emitted(&hit_record);
+                spectrum += throughput * upsample(&emission);
+
+                // Only the hero wavelength can follow the refracted direction or chromatic medium
+                if ray.wavelength.is_none()
+                    && (hit_record.material.is_dispersive() || hit_record.material.is_chromatic())
+                {
+                    throughput = throughput.hero_only();
+                    ray.wavelength = Some(wavelengths.hero());
+                }
+
+                if let Some((attenuation, scattered)) =
+                    hit_record.material.scatter(&ray, &hit_record)
+                {
+                    let (attenuation, scattered) =
+                        self.sample_background(&ray, &hit_record, attenuation, scattered);
+
+                    throughput = throughput * upsample(&attenuation);
+                    ray = scattered.with_wavelength(ray.wavelength);
+                } else {
+                    break;
+                }
+            } else {
+                spectrum += throughput * upsample(&self.background.value(&ray.direction));
+                break;
+            }
+        }
+
+        spectrum.to_rgb(&wavelengths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completed_regions_are_clipped_to_the_image() {
+        let mut completed = CompletionMap {
+            chunk_size: 16,
+            chunks_x: 3,
+            chunks_y: 2,
+            completed: vec![false; 6],
+        };
+        completed.completed[2] = true;
+        completed.completed[4] = true;
+
+        assert!(!completed.is_complete());
+        assert!(completed.is_pixel_done(5, 40));
+        assert!(!completed.is_pixel_done(5, 20));
+        assert_eq!(
+            completed.completed_regions(40, 20),
+            vec![[32, 0, 40, 16], [16, 16, 32, 20]]
+        );
+    }
 }
@@ -0,0 +1,63 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Shared flag for stopping a render from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Conditions under which a render stops scheduling new chunks
+#[derive(Debug, Clone, Default)]
+pub struct RenderLimits {
+    cancellation: Option<CancellationToken>,
+    time_budget: Option<Duration>,
+}
+
+impl RenderLimits {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = Some(time_budget);
+        self
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+    }
+
+    pub fn should_stop(&self, start_time: Instant) -> bool {
+        self.is_cancelled()
+            || self
+                .time_budget
+                .is_some_and(|budget| start_time.elapsed() >= budget)
+    }
+}
@@ -0,0 +1,210 @@
+/// Piecewise-constant distribution over [0, 1) proportional to a tabulated function
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    function: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// Negative values are treated as zero, an all-zero function becomes uniform
+    pub fn new(function: Vec<f64>) -> Self {
+        let n = function.len();
+        let function: Vec<f64> = function.into_iter().map(|f| f.max(0.0)).collect();
+
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for (i, f) in function.iter().enumerate() {
+            cdf.push(cdf[i] + f / n as f64);
+        }
+
+        let integral = cdf[n];
+        if integral > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            cdf.iter_mut()
+                .enumerate()
+                .for_each(|(i, c)| *c = i as f64 / n as f64);
+        }
+
+        Self {
+            function,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.function.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.function.is_empty()
+    }
+
+    /// Average of the function over [0, 1)
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Maps a uniform `u` to a point in [0, 1), returning it with its pdf and bin
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // Last bin whose cdf is at most u
+        let bin = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.len() - 1);
+
+        let (start, end) = (self.cdf[bin], self.cdf[bin + 1]);
+        let offset = if end > start {
+            (u - start) / (end - start)
+        } else {
+            0.5
+        };
+
+        let x = ((bin as f64 + offset) / self.len() as f64).min(1.0 - f64::EPSILON);
+
+        (x, self.pdf_bin(bin), bin)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let bin = ((x * self.len() as f64) as usize).min(self.len() - 1);
+
+        self.pdf_bin(bin)
+    }
+
+    fn pdf_bin(&self, bin: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.function[bin] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Piecewise-constant distribution over [0, 1)², sampled by row then column (Pharr et al., pbrt)
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `function` holds `height` rows of `width` values
+    pub fn new(function: &[f64], width: usize, height: usize) -> Self {
+        let rows: Vec<Distribution1D> = (0..height)
+            .map(|y| Distribution1D::new(function[y * width..(y + 1) * width].to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.integral()).collect());
+
+        Self { rows, marginal }
+    }
+
+    /// Returns a point in [0, 1)² and its pdf
+    pub fn sample(&self, u: (f64, f64)) -> ((f64, f64), f64) {
+        let (y, pdf_y, row) = self.marginal.sample(u.1);
+        let (x, pdf_x, _) = self.rows[row].sample(u.0);
+
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    pub fn pdf(&self, point: (f64, f64)) -> f64 {
+        let row = ((point.1 * self.rows.len() as f64) as usize).min(self.rows.len() - 1);
+
+        self.marginal.pdf(point.1) * self.rows[row].pdf(point.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEPS: usize = 1000;
+
+    /// Midpoint points across [0, 1)
+    fn midpoints() -> impl Iterator<Item = f64> {
+        (0..STEPS).map(|i| (i as f64 + 0.5) / STEPS as f64)
+    }
+
+    fn distribution() -> Distribution1D {
+        Distribution1D::new(vec![1.0, 0.0, 3.0, -2.0, 0.5])
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let integral: f64 = midpoints().map(|x| distribution().pdf(x)).sum::<f64>() / STEPS as f64;
+
+        assert!((integral - 1.0).abs() < 1e-9, "{}", integral);
+    }
+
+    #[test]
+    fn samples_report_their_pdf() {
+        let distribution = distribution();
+
+        for u in midpoints() {
+            let (x, pdf, bin) = distribution.sample(u);
+
+            assert!((0.0..1.0).contains(&x));
+            assert_eq!(bin, (x * distribution.len() as f64) as usize);
+            assert_eq!(pdf, distribution.pdf(x));
+            assert!(pdf > 0.0);
+        }
+    }
+
+    #[test]
+    fn samples_follow_the_function() {
+        let distribution = distribution();
+        let mut histogram = [0.0; 5];
+
+        for u in midpoints() {
+            histogram[distribution.sample(u).2] += 1.0 / STEPS as f64;
+        }
+
+        for (bin, expected) in [1.0, 0.0, 3.0, 0.0, 0.5].into_iter().enumerate() {
+            assert!((histogram[bin] - expected / 4.5).abs() < 2e-3);
+        }
+    }
+
+    #[test]
+    fn zero_functions_become_uniform() {
+        let distribution = Distribution1D::new(vec![0.0; 4]);
+
+        for u in midpoints() {
+            let (x, pdf, _) = distribution.sample(u);
+
+            assert!((x - u).abs() < 1e-12);
+            assert_eq!(pdf, 1.0);
+        }
+    }
+
+    #[test]
+    fn pdf_2d_integrates_to_one_and_matches_samples() {
+        let function = [0.0, 1.0, 2.0, 4.0, 0.5, 0.0, 1.0, 3.0, 2.0];
+        let distribution = Distribution2D::new(&function, 3, 3);
+
+        // Midpoints of a grid aligned with the 3x3 cells
+        let n = 300;
+        let grid = |i: usize| (i as f64 + 0.5) / n as f64;
+
+        let mut integral = 0.0;
+        let mut histogram = [0.0; 9];
+        for y in 0..n {
+            for x in 0..n {
+                integral += distribution.pdf((grid(x), grid(y))) / (n * n) as f64;
+
+                let (point, pdf) = distribution.sample((grid(x), grid(y)));
+                assert_eq!(pdf, distribution.pdf(point));
+                assert!(pdf > 0.0);
+
+                let cell = (point.1 * 3.0) as usize * 3 + (point.0 * 3.0) as usize;
+                histogram[cell] += 1.0 / (n * n) as f64;
+            }
+        }
+
+        assert!((integral - 1.0).abs() < 1e-9, "{}", integral);
+        for (cell, f) in function.iter().enumerate() {
+            assert!((histogram[cell] - f / 13.5).abs() < 1e-2);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::image::color::Color;
+
+/// Unpolarised reflectance of a dielectric interface, `eta` being the ratio of transmitted over incident ior
+pub fn dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+
+    let sin_2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin_2_t >= 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin_2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+/// Unpolarised reflectance of a conductor with complex ior `eta + ik`, seen from a medium with ior 1
+pub fn conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+
+    let cos_2 = cos_i * cos_i;
+    let sin_2 = 1.0 - cos_2;
+    let eta_2 = eta * eta;
+    let k_2 = k * k;
+
+    let t0 = eta_2 - k_2 - sin_2;
+    let a_2_plus_b_2 = (t0 * t0 + 4.0 * eta_2 * k_2).sqrt();
+    let t1 = a_2_plus_b_2 + cos_2;
+    let a = (0.5 * (a_2_plus_b_2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_i * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos_2 * a_2_plus_b_2 + sin_2 * sin_2;
+    let t4 = t2 * sin_2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+
+    0.5 * (r_p + r_s)
+}
+
+pub fn conductor_color(cos_i: f64, eta: &Color, k: &Color) -> Color {
+    Color::new(
+        conductor(cos_i, eta.r, k.r),
+        conductor(cos_i, eta.g, k.g),
+        conductor(cos_i, eta.b, k.b),
+    )
+}
+
+/// Finds a complex ior with the given normal incidence reflectance and edge tint (Gulbrandsen 2014)
+pub fn conductor_from_reflectance(reflectance: f64, edge_tint: f64) -> (f64, f64) {
+    let r = reflectance.clamp(0.0, 0.99);
+    let g = edge_tint.clamp(0.0, 1.0);
+
+    let n_min = (1.0 - r) / (1.0 + r);
+    let n_max = (1.0 + r.sqrt()) / (1.0 - r.sqrt());
+    let eta = g * n_min + (1.0 - g) * n_max;
+
+    let k_2 = ((eta + 1.0).powi(2) * r - (eta - 1.0).powi(2)) / (1.0 - r);
+
+    (eta, k_2.max(0.0).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dielectric_matches_normal_incidence_closed_form() {
+        for eta in [1.33_f64, 1.5, 2.4, 1.0 / 1.5] {
+            let expected = ((eta - 1.0) / (eta + 1.0)).powi(2);
+
+            assert!((dielectric(1.0, eta) - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn dielectric_reflects_totally_past_the_critical_angle() {
+        let critical_cos = (1.0_f64 - 1.0 / (1.5 * 1.5)).sqrt();
+
+        assert_eq!(dielectric(critical_cos - 1e-3, 1.0 / 1.5), 1.0);
+        assert!(dielectric(critical_cos + 1e-3, 1.0 / 1.5) < 1.0);
+    }
+
+    #[test]
+    fn conductor_matches_normal_incidence_closed_form() {
+        // Gold, copper and aluminium at around 550 nm
+        for (eta, k) in [(0.43_f64, 2.45_f64), (1.02, 2.58), (0.96, 6.69)] {
+            let expected =
+                ((eta - 1.0) * (eta - 1.0) + k * k) / ((eta + 1.0) * (eta + 1.0) + k * k);
+
+            assert!((conductor(1.0, eta, k) - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn conductor_reflects_fully_at_grazing_angles() {
+        assert!((conductor(0.0, 0.43, 2.45) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn conductor_from_reflectance_round_trips() {
+        for (reflectance, edge_tint) in [(0.9, 0.9), (0.5, 0.2), (0.04, 1.0)] {
+            let (eta, k) = conductor_from_reflectance(reflectance, edge_tint);
+
+            assert!((conductor(1.0, eta, k) - reflectance).abs() < 1e-9);
+        }
+    }
+}
@@ -3,6 +3,7 @@ pub mod bvh;
 pub mod constant_medium;
 pub mod quad;
 pub mod sphere;
+pub mod subsurface;
 pub mod transform;
 
 use std::sync::Arc;
@@ -12,6 +13,7 @@ use bvh::BVHNode;
 
 use crate::{interval::Interval, material::Material, ray::Ray, vec3::Vec3};
 
+#[derive(Clone)]
 pub struct HitRecord {
     pub pos: Vec3,
     pub normal: Vec3,
@@ -19,6 +21,15 @@ pub struct HitRecord {
     pub t: f64,
     pub uv: (f64, f64),
     pub front_face: bool,
+    // Partial derivatives of the position along u and v, zero when the surface has none
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    // Offsets to the hits of the ray's differentials, on the tangent plane
+    pub dpdx: Vec3,
+    pub dpdy: Vec3,
+    // Extent of the pixel footprint in u and v, zero without ray differentials
+    pub du: f64,
+    pub dv: f64,
 }
 
 impl HitRecord {
@@ -30,6 +41,12 @@ impl HitRecord {
             t,
             uv,
             front_face: false,
+            dpdu: Vec3::zero(),
+            dpdv: Vec3::zero(),
+            dpdx: Vec3::zero(),
+            dpdy: Vec3::zero(),
+            du: 0.0,
+            dv: 0.0,
         }
     }
 
@@ -44,6 +61,57 @@ impl HitRecord {
             -outward_normal
         };
     }
+
+    /// Finds the footprint of the ray's differentials on the tangent plane (Igehy 1999)
+    pub fn compute_differentials(&mut self, ray: &Ray) {
+        let Some(differentials) = &ray.differentials else {
+            return;
+        };
+
+        let d = self.normal.dot(&self.pos);
+        let offset = |origin: &Vec3, direction: &Vec3| {
+            let denominator = self.normal.dot(direction);
+            if denominator.abs() < 1e-12 {
+                return None;
+            }
+
+            let t = (d - self.normal.dot(origin)) / denominator;
+            Some(*origin + t * *direction - self.pos)
+        };
+
+        let (Some(dpdx), Some(dpdy)) = (
+            offset(&differentials.rx_origin, &differentials.rx_direction),
+            offset(&differentials.ry_origin, &differentials.ry_direction),
+        ) else {
+            return;
+        };
+
+        self.dpdx = dpdx;
+        self.dpdy = dpdy;
+
+        // Least squares fit of dpdx and dpdy in terms of dpdu and dpdv
+        let a_00 = self.dpdu.dot(&self.dpdu);
+        let a_01 = self.dpdu.dot(&self.dpdv);
+        let a_11 = self.dpdv.dot(&self.dpdv);
+        let determinant = a_00 * a_11 - a_01 * a_01;
+        if determinant.abs() < 1e-12 {
+            return;
+        }
+
+        let solve = |dp: &Vec3| {
+            let (b_0, b_1) = (self.dpdu.dot(dp), self.dpdv.dot(dp));
+            (
+                (a_11 * b_0 - a_01 * b_1) / determinant,
+                (a_00 * b_1 - a_01 * b_0) / determinant,
+            )
+        };
+
+        let (dudx, dvdx) = solve(&dpdx);
+        let (dudy, dvdy) = solve(&dpdy);
+
+        self.du = dudx.abs().max(dudy.abs());
+        self.dv = dvdx.abs().max(dvdy.abs());
+    }
 }
 
 pub trait Hittable: Send + Sync {
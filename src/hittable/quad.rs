@@ -1,6 +1,12 @@
 use std::sync::Arc;
 
-use crate::{interval::Interval, material::Material, ray::Ray, vec3::Vec3};
+use crate::{
+    image::color::Color,
+    interval::Interval,
+    material::{light::LightUnit, Material},
+    ray::Ray,
+    vec3::Vec3,
+};
 
 use super::{aabb::AABB, HitRecord, Hittable, HittableList};
 
@@ -84,6 +90,27 @@ impl Quad {
         }
     }
 
+    /// Light emitting `unit` from its front face, keeping its power when the quad is resized
+    pub fn light(corner: Vec3, u: Vec3, v: Vec3, color: Color, unit: LightUnit) -> Self {
+        let mut quad = Self::new(
+            corner,
+            u,
+            v,
+            Arc::new(Material::lambertian_from_color(color)),
+        );
+        quad.material = Arc::new(Material::diffuse_light_photometric(
+            color,
+            unit,
+            quad.area(),
+        ));
+
+        quad
+    }
+
+    pub fn area(&self) -> f64 {
+        self.u.cross(&self.v).length()
+    }
+
     fn find_bounding_box(corner: Vec3, u: Vec3, v: Vec3) -> AABB {
         let bounding_box_diagonal_1 = AABB::from_corners(corner, corner + u + v);
         let bounding_box_diagonal_2 = AABB::from_corners(corner + u, corner + v);
@@ -123,10 +150,16 @@ impl Hittable for Quad {
         //     return false;
         // }
 
+        if !self.material.is_opaque_at((alpha, beta), &intersection) {
+            return None;
+        }
+
         // Create hit record only after confirming it's a valid hit
         let mut hit_record =
             HitRecord::new(intersection, t, Arc::clone(&self.material), (alpha, beta));
         hit_record.set_face_normal(ray, self.normal);
+        hit_record.dpdu = self.u;
+        hit_record.dpdv = self.v;
 
         Some(hit_record)
     }
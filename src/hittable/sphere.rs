@@ -1,11 +1,18 @@
 use std::{f64::consts::PI, sync::Arc};
 
-use crate::{interval::Interval, material::Material, ray::Ray, vec3::Vec3};
+use crate::{
+    animation::{Interpolation, Track},
+    image::color::Color,
+    interval::Interval,
+    material::{light::LightUnit, Material},
+    ray::Ray,
+    vec3::Vec3,
+};
 
 use super::{aabb::AABB, HitRecord, Hittable};
 
 pub struct Sphere {
-    center: Ray,
+    center: Track<Vec3>,
     radius: f64,
     material: Arc<Material>,
     bounding_box: AABB,
@@ -13,41 +20,89 @@ pub struct Sphere {
 
 impl Sphere {
     pub fn still(center: Vec3, radius: f64, material: Arc<Material>) -> Self {
-        let r_vec = Vec3::new(radius, radius, radius);
-
-        Self {
-            center: Ray::new(center, Vec3::zero(), 0.0),
-            radius: radius.max(0.0),
-            material,
-            bounding_box: AABB::from_corners(center - r_vec, center + r_vec),
-        }
+        Self::animated(Track::constant(center), radius, material)
     }
 
+    /// Moves from `start` at time 0 to `end` at time 1 and rests there afterwards
     pub fn moving(start: Vec3, end: Vec3, radius: f64, material: Arc<Material>) -> Self {
+        let center = Track::new(0.0, start, Interpolation::Linear).key(1.0, end);
+
+        Self::animated(center, radius, material)
+    }
+
+    /// Center keyframed over time in seconds, matching the camera's shutter times
+    pub fn animated(center: Track<Vec3>, radius: f64, material: Arc<Material>) -> Self {
+        let radius = radius.max(0.0);
         let r_vec = Vec3::new(radius, radius, radius);
 
-        let box_1 = AABB::from_corners(start - r_vec, start + r_vec);
-        let box_2 = AABB::from_corners(end - r_vec, end + r_vec);
+        let mut bounding_box = AABB::empty();
+        for position in center.control_points() {
+            bounding_box = AABB::from_aabbs(
+                &bounding_box,
+                &AABB::from_corners(position - r_vec, position + r_vec),
+            );
+        }
 
         Self {
-            center: Ray::new(start, end - start, 0.0),
+            center,
             radius,
             material,
-            bounding_box: AABB::from_aabbs(&box_1, &box_2),
+            bounding_box,
         }
     }
 
+    /// Light emitting `unit` outwards, keeping its power when the radius changes
+    pub fn light(center: Vec3, radius: f64, color: Color, unit: LightUnit) -> Self {
+        let mut sphere = Self::still(
+            center,
+            radius,
+            Arc::new(Material::lambertian_from_color(color)),
+        );
+        sphere.material = Arc::new(Material::diffuse_light_photometric(
+            color,
+            unit,
+            sphere.area(),
+        ));
+
+        sphere
+    }
+
+    pub fn area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+
     fn get_sphere_uv(normal: &Vec3) -> (f64, f64) {
         let theta = (-normal.y).acos();
         let phi = (-normal.z).atan2(normal.x) + PI;
 
         (phi / (2.0 * PI), theta / PI)
     }
+
+    /// Derivatives of the position along the uv parametrisation of `get_sphere_uv`
+    fn get_sphere_tangents(&self, normal: &Vec3) -> (Vec3, Vec3) {
+        let dpdu = 2.0 * PI * self.radius * Vec3::new(normal.z, 0.0, -normal.x);
+
+        // Undefined at the poles
+        let sin_theta = (normal.x * normal.x + normal.z * normal.z).sqrt();
+        if sin_theta == 0.0 {
+            return (dpdu, Vec3::zero());
+        }
+
+        let dpdv = PI
+            * self.radius
+            * Vec3::new(
+                -normal.y * normal.x / sin_theta,
+                sin_theta,
+                -normal.y * normal.z / sin_theta,
+            );
+
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let center = self.center.at(ray.time);
+        let center = self.center.sample(ray.time);
 
         let oc = center - ray.origin;
 
@@ -64,24 +119,28 @@ impl Hittable for Sphere {
         // Discriminant >= 0 -> intersection (= 0 -> 1 intersection, > 0 -> 2 intersections)
         let discriminant_sqrt = discriminant.sqrt();
 
-        let mut root = (h - discriminant_sqrt) / a;
-        // If one root is not a hit, try the other
-        if !ray_t.surrounds(root) {
-            root = (h + discriminant_sqrt) / a;
-
+        // Nearest root first, falling back to the other when it misses or is cut out
+        for root in [(h - discriminant_sqrt) / a, (h + discriminant_sqrt) / a] {
             if !ray_t.surrounds(root) {
-                return None;
+                continue;
             }
-        }
 
-        let hit_pos = ray.at(root);
-        let outward_normal = (hit_pos - center) / self.radius;
+            let hit_pos = ray.at(root);
+            let outward_normal = (hit_pos - center) / self.radius;
+
+            let uv = Self::get_sphere_uv(&outward_normal);
+            if !self.material.is_opaque_at(uv, &hit_pos) {
+                continue;
+            }
+
+            let mut hit_record = HitRecord::new(hit_pos, root, Arc::clone(&self.material), uv);
+            hit_record.set_face_normal(ray, outward_normal);
+            (hit_record.dpdu, hit_record.dpdv) = self.get_sphere_tangents(&outward_normal);
 
-        let uv = Self::get_sphere_uv(&outward_normal);
-        let mut hit_record = HitRecord::new(hit_pos, root, Arc::clone(&self.material), uv);
-        hit_record.set_face_normal(ray, outward_normal);
+            return Some(hit_record);
+        }
 
-        Some(hit_record)
+        None
     }
 
     fn bounding_box(&self) -> &AABB {
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use crate::{
+    image::color::Color, interval::Interval, material::Material, random::gen_f64, ray::Ray,
+    spectrum,
+};
+
+use super::{aabb::AABB, HitRecord, Hittable};
+
+/// Random walk subsurface scattering inside a closed boundary
+///
+/// Rays refract in through the boundary, then scatter isotropically until they leave it again.
+/// Each walk is traced in a single colour channel (or wavelength in spectral mode), so its distances can be
+/// sampled exactly.
+pub struct Subsurface {
+    boundary: Arc<dyn Hittable>,
+    // Per unit length, the reciprocal of the mean free path
+    extinction: Color,
+    interface: Arc<Material>,
+    phase_function: Arc<Material>,
+}
+
+impl Subsurface {
+    /// `albedo` is the single scattering albedo, `mean_free_path` the average distance between events per channel
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        albedo: Color,
+        mean_free_path: Color,
+        refraction_index: f64,
+    ) -> Self {
+        let extinction = Color::new(
+            1.0 / mean_free_path.r.max(1e-6),
+            1.0 / mean_free_path.g.max(1e-6),
+            1.0 / mean_free_path.b.max(1e-6),
+        );
+
+        Self {
+            boundary,
+            extinction,
+            interface: Arc::new(Material::subsurface(extinction, refraction_index)),
+            phase_function: Arc::new(Material::subsurface_phase(albedo)),
+        }
+    }
+}
+
+impl Hittable for Subsurface {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let mut hit = self.boundary.hit(ray, ray_t)?;
+
+        // Entering from outside
+        if hit.front_face {
+            hit.material = Arc::clone(&self.interface);
+            return Some(hit);
+        }
+
+        let extinction = match (ray.wavelength, ray.channel) {
+            (Some(lambda), _) => spectrum::rgb_to_spectrum(&self.extinction, lambda),
+            (None, Some(channel)) => self.extinction.get(channel),
+            (None, None) => (self.extinction.r + self.extinction.g + self.extinction.b) / 3.0,
+        };
+
+        // Rays inside start at the previous event, so distances are measured from the origin
+        let ray_length = ray.direction.length();
+        let distance_inside_boundary = hit.t * ray_length;
+        let hit_distance = -(1.0 - gen_f64()).ln() / extinction.max(1e-6);
+
+        // Leaving before the next scattering event
+        if hit_distance >= distance_inside_boundary {
+            hit.material = Arc::clone(&self.interface);
+            return Some(hit);
+        }
+
+        let t = hit_distance / ray_length;
+        let hit = HitRecord::new(ray.at(t), t, Arc::clone(&self.phase_function), (0.0, 0.0));
+
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        self.boundary.bounding_box()
+    }
+}
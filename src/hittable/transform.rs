@@ -1,34 +1,58 @@
 use std::sync::Arc;
 
-use crate::{interval::Interval, ray::Ray, vec3::Vec3};
+use crate::{
+    animation::Track,
+    interval::Interval,
+    ray::{Ray, RayDifferentials},
+    vec3::Vec3,
+};
 
 use super::{aabb::AABB, HitRecord, Hittable};
 
 pub struct Translate {
     object: Arc<dyn Hittable>,
-    offset: Vec3,
+    offset: Track<Vec3>,
     bounding_box: AABB,
 }
 
 impl Translate {
     pub fn new(object: Arc<dyn Hittable>, offset: Vec3) -> Self {
-        let bbox = object.bounding_box() + offset;
+        Self::animated(object, Track::constant(offset))
+    }
+
+    pub fn animated(object: Arc<dyn Hittable>, offset: Track<Vec3>) -> Self {
+        let mut bounding_box = AABB::empty();
+        for point in offset.control_points() {
+            bounding_box = AABB::from_aabbs(&bounding_box, &(object.bounding_box() + point));
+        }
+
         Self {
             object,
             offset,
-            bounding_box: bbox,
+            bounding_box,
         }
     }
 }
 
 impl Hittable for Translate {
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let offset_ray = Ray::new(ray.origin - self.offset, ray.direction, ray.time);
+        let offset = self.offset.sample(ray.time);
+
+        let differentials = ray.differentials.map(|d| RayDifferentials {
+            rx_origin: d.rx_origin - offset,
+            ry_origin: d.ry_origin - offset,
+            ..d
+        });
+        // Media inside still need the path's wavelength and channel
+        let offset_ray = Ray::new(ray.origin - offset, ray.direction, ray.time)
+            .with_wavelength(ray.wavelength)
+            .with_channel(ray.channel)
+            .with_differentials(differentials);
 
         let hit = self.object.hit(&offset_ray, ray_t);
 
         if let Some(mut hit) = hit {
-            hit.pos += self.offset;
+            hit.pos += offset;
             return Some(hit);
         }
 
@@ -42,6 +66,7 @@ impl Hittable for Translate {
 
 pub struct RotateY {
     object: Arc<dyn Hittable>,
+    angle: Track<f64>,
     sin_theta: f64,
     cos_theta: f64,
     bounding_box: AABB,
@@ -49,13 +74,30 @@ pub struct RotateY {
 
 impl RotateY {
     pub fn new(object: Arc<dyn Hittable>, angle: f64) -> Self {
-        let radians = angle.to_radians();
+        Self::animated(object, Track::constant(angle))
+    }
+
+    /// Rotation angle in degrees keyframed over time
+    pub fn animated(object: Arc<dyn Hittable>, angle: Track<f64>) -> Self {
+        let (sin_theta, cos_theta) = angle.sample(0.0).to_radians().sin_cos();
 
-        let sin_theta = radians.sin();
-        let cos_theta = radians.cos();
+        let bounding_box = if angle.is_constant() {
+            Self::rotated_bounding_box(object.bounding_box(), sin_theta, cos_theta)
+        } else {
+            // Corners sweep arcs between keys, so bound the cylinder they turn within
+            Self::swept_bounding_box(object.bounding_box())
+        };
 
-        let bounding_box = object.bounding_box().clone();
+        Self {
+            object,
+            angle,
+            sin_theta,
+            cos_theta,
+            bounding_box,
+        }
+    }
 
+    fn rotated_bounding_box(bounding_box: &AABB, sin_theta: f64, cos_theta: f64) -> AABB {
         let mut min = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
         let mut max = Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
 
@@ -81,44 +123,77 @@ impl RotateY {
             }
         }
 
-        Self {
-            object,
-            sin_theta,
-            cos_theta,
-            bounding_box: AABB::from_corners(min, max),
+        AABB::from_corners(min, max)
+    }
+
+    fn swept_bounding_box(bounding_box: &AABB) -> AABB {
+        let x = bounding_box.x.min.abs().max(bounding_box.x.max.abs());
+        let z = bounding_box.z.min.abs().max(bounding_box.z.max.abs());
+        let radius = (x * x + z * z).sqrt();
+
+        AABB::from_corners(
+            Vec3::new(-radius, bounding_box.y.min, -radius),
+            Vec3::new(radius, bounding_box.y.max, radius),
+        )
+    }
+
+    fn sin_cos(&self, time: f64) -> (f64, f64) {
+        if self.angle.is_constant() {
+            (self.sin_theta, self.cos_theta)
+        } else {
+            self.angle.sample(time).to_radians().sin_cos()
         }
     }
 }
 
 impl Hittable for RotateY {
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let origin = Vec3::new(
-            (self.cos_theta * ray.origin.x) - (self.sin_theta * ray.origin.z),
-            ray.origin.y,
-            (self.sin_theta * ray.origin.x) + (self.cos_theta * ray.origin.z),
-        );
-
-        let direction = Vec3::new(
-            (self.cos_theta * ray.direction.x) - (self.sin_theta * ray.direction.z),
-            ray.direction.y,
-            (self.sin_theta * ray.direction.x) + (self.cos_theta * ray.direction.z),
-        );
-
-        let rotated_ray = Ray::new(origin, direction, ray.time);
+        let (sin_theta, cos_theta) = self.sin_cos(ray.time);
+
+        let rotate = |v: &Vec3| {
+            Vec3::new(
+                (cos_theta * v.x) - (sin_theta * v.z),
+                v.y,
+                (sin_theta * v.x) + (cos_theta * v.z),
+            )
+        };
+
+        let differentials = ray.differentials.map(|d| RayDifferentials {
+            rx_origin: rotate(&d.rx_origin),
+            rx_direction: rotate(&d.rx_direction),
+            ry_origin: rotate(&d.ry_origin),
+            ry_direction: rotate(&d.ry_direction),
+        });
+        let rotated_ray = Ray::new(rotate(&ray.origin), rotate(&ray.direction), ray.time)
+            .with_wavelength(ray.wavelength)
+            .with_channel(ray.channel)
+            .with_differentials(differentials);
 
         let hit = self.object.hit(&rotated_ray, ray_t);
 
         if let Some(mut hit) = hit {
             hit.pos = Vec3::new(
-                (self.cos_theta * hit.pos.x) + (self.sin_theta * hit.pos.z),
+                (cos_theta * hit.pos.x) + (sin_theta * hit.pos.z),
                 hit.pos.y,
-                (-self.sin_theta * hit.pos.x) + (self.cos_theta * hit.pos.z),
+                (-sin_theta * hit.pos.x) + (cos_theta * hit.pos.z),
             );
 
             hit.normal = Vec3::new(
-                (self.cos_theta * hit.normal.x) + (self.sin_theta * hit.normal.z),
+                (cos_theta * hit.normal.x) + (sin_theta * hit.normal.z),
                 hit.normal.y,
-                (-self.sin_theta * hit.normal.x) + (self.cos_theta * hit.normal.z),
+                (-sin_theta * hit.normal.x) + (cos_theta * hit.normal.z),
+            );
+
+            hit.dpdu = Vec3::new(
+                (cos_theta * hit.dpdu.x) + (sin_theta * hit.dpdu.z),
+                hit.dpdu.y,
+                (-sin_theta * hit.dpdu.x) + (cos_theta * hit.dpdu.z),
+            );
+
+            hit.dpdv = Vec3::new(
+                (cos_theta * hit.dpdv.x) + (sin_theta * hit.dpdv.z),
+                hit.dpdv.y,
+                (-sin_theta * hit.dpdv.x) + (cos_theta * hit.dpdv.z),
             );
 
             return Some(hit);
@@ -38,6 +38,21 @@ impl Color {
 }
 
 impl Color {
+    pub fn get(&self, n: usize) -> f64 {
+        if n == 0 {
+            self.r
+        } else if n == 1 {
+            self.g
+        } else {
+            self.b
+        }
+    }
+
+    /// Relative luminance of linear sRGB
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
     fn linear_to_gamma_2(&self) -> Self {
         Self {
             r: self.r.max(0.0).sqrt(),
@@ -46,7 +61,7 @@ impl Color {
         }
     }
 
-    pub fn as_u8_string(&self) -> String {
+    pub fn as_u8(&self) -> [u8; 3] {
         static INTENSITY: Interval = Interval {
             min: 0.0,
             max: 0.999999,
@@ -58,6 +73,12 @@ impl Color {
         let g = (256.0 * INTENSITY.clamp(gamma_color.g)) as u8;
         let b = (256.0 * INTENSITY.clamp(gamma_color.b)) as u8;
 
+        [r, g, b]
+    }
+
+    pub fn as_u8_string(&self) -> String {
+        let [r, g, b] = self.as_u8();
+
         format!("{} {} {}", r, g, b)
     }
 }
@@ -6,7 +6,7 @@ use std::{
 };
 
 use color::Color;
-use image::{open, RgbImage};
+use image::{open, DynamicImage, Rgb, RgbImage};
 
 #[derive(Debug)]
 pub struct Image {
@@ -46,6 +46,18 @@ impl Image {
         fs::write(path, data).expect("Unable to write to file");
     }
 
+    pub fn save_png(&self, name: &str) -> Result<(), String> {
+        let file_name = format!("{}.png", name);
+
+        let image = RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            Rgb(self.image_data[y as usize * self.width + x as usize].as_u8())
+        });
+
+        image
+            .save(&file_name)
+            .map_err(|e| format!("Unable to write {}: {}", file_name, e))
+    }
+
     pub fn set_pixel(&mut self, color: Color, row: usize, col: usize) {
         let index = row * self.width + col;
 
@@ -53,53 +65,435 @@ impl Image {
     }
 }
 
-pub struct ExtImage {
-    image: RgbImage,
+/// Encoding of an image's stored colour values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
+impl ColorSpace {
+    fn to_linear(self, value: f32) -> f32 {
+        match self {
+            ColorSpace::Linear => value,
+            ColorSpace::Srgb => {
+                if value <= 0.04045 {
+                    value / 12.92
+                } else {
+                    ((value + 0.055) / 1.055).powf(2.4)
+                }
+            }
+        }
+    }
+}
+
+/// Reconstruction filter used when sampling an `ExtImage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFilter {
+    Nearest,
+    Bilinear,
+    /// Catmull-Rom spline over the surrounding 4x4 texels
+    Bicubic,
+}
+
+/// How texel coordinates outside the image are mapped back into it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+impl WrapMode {
+    fn wrap(&self, x: i64, size: usize) -> usize {
+        let size = size as i64;
+
+        match self {
+            WrapMode::Repeat => x.rem_euclid(size) as usize,
+            WrapMode::Mirror => {
+                let x = x.rem_euclid(2 * size);
+                (if x < size { x } else { 2 * size - 1 - x }) as usize
+            }
+            WrapMode::Clamp => x.clamp(0, size - 1) as usize,
+        }
+    }
+}
+
+/// One level of a mip pyramid, RGBA texels in rows from the top
+struct MipLevel {
     width: usize,
     height: usize,
+    texels: Vec<[f32; 4]>,
+}
+
+impl MipLevel {
+    /// Box filters down to half the size, rounding up so odd edges are kept
+    fn downsample(&self) -> Self {
+        let width = self.width.div_ceil(2);
+        let height = self.height.div_ceil(2);
+        let mut texels = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 4];
+
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sx = (2 * x + dx).min(self.width - 1);
+                    let sy = (2 * y + dy).min(self.height - 1);
+                    let texel = self.texels[sy * self.width + sx];
+
+                    for c in 0..4 {
+                        sum[c] += 0.25 * texel[c];
+                    }
+                }
+
+                texels.push(sum);
+            }
+        }
+
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+/// Image loaded for texturing, with a precomputed mip pyramid
+pub struct ExtImage {
+    levels: Vec<MipLevel>,
+    // Encoding of the source file, texels are stored decoded to linear
+    color_space: ColorSpace,
+    filter: ImageFilter,
+    // Horizontal and vertical wrap modes
+    wrap: (WrapMode, WrapMode),
 }
 
 impl ExtImage {
+    /// Loads an image, taking float formats (HDR, EXR) as linear and integer formats as sRGB
     pub fn load(file_name: &str) -> Result<Self, String> {
+        let image = Self::open(file_name)?;
+
+        let color_space = match image {
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => ColorSpace::Linear,
+            _ => ColorSpace::Srgb,
+        };
+
+        Ok(Self::from_dynamic(image, color_space))
+    }
+
+    /// Loads an image stored in the given color space, e.g. linear for normal maps and other data
+    pub fn load_with(file_name: &str, color_space: ColorSpace) -> Result<Self, String> {
+        Ok(Self::from_dynamic(Self::open(file_name)?, color_space))
+    }
+
+    fn open(file_name: &str) -> Result<DynamicImage, String> {
         let path = Path::new(file_name);
 
         if !path.is_file() {
             return Err(format!("File `{}` not found", file_name));
         }
 
-        let image = match open(path) {
-            Ok(image) => image.to_rgb8(),
-            Err(_) => return Err(format!("Could not load `{}`", path.to_str().unwrap())),
-        };
+        match open(path) {
+            Ok(image) => Ok(image),
+            Err(_) => Err(format!("Could not load `{}`", path.to_str().unwrap())),
+        }
+    }
+
+    /// Converts 8-bit, 16-bit and float images alike, decoding sRGB so texels are always linear
+    fn from_dynamic(image: DynamicImage, color_space: ColorSpace) -> Self {
+        let image = image.to_rgba32f();
 
-        let width = image.width() as usize;
-        let height = image.height() as usize;
+        let texels = image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b, a] = pixel.0;
+                let decode = |c: f32| color_space.to_linear(c);
 
-        Ok(Self {
-            image,
+                // Alpha is always linear
+                [decode(r), decode(g), decode(b), a]
+            })
+            .collect();
+
+        let mut ext_image =
+            Self::from_texels(image.width() as usize, image.height() as usize, texels);
+        ext_image.color_space = color_space;
+
+        ext_image
+    }
+
+    /// Builds the mip pyramid for linear RGBA texels given in rows from the top
+    pub fn from_texels(width: usize, height: usize, texels: Vec<[f32; 4]>) -> Self {
+        let mut levels = vec![MipLevel {
             width,
             height,
-        })
+            texels,
+        }];
+
+        while let Some(last) = levels.last() {
+            if last.width <= 1 && last.height <= 1 {
+                break;
+            }
+
+            levels.push(last.downsample());
+        }
+
+        Self {
+            levels,
+            color_space: ColorSpace::Linear,
+            filter: ImageFilter::Bilinear,
+            wrap: (WrapMode::Clamp, WrapMode::Clamp),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: ImageFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = (wrap, wrap);
+        self
+    }
+
+    /// Separate wrap modes along u and v, e.g. for panoramas that only repeat horizontally
+    pub fn with_wrap_uv(mut self, wrap_u: WrapMode, wrap_v: WrapMode) -> Self {
+        self.wrap = (wrap_u, wrap_v);
+        self
     }
 
     pub fn width(&self) -> usize {
-        self.width
+        self.levels[0].width
     }
 
     pub fn height(&self) -> usize {
-        self.height
+        self.levels[0].height
     }
 
-    pub fn pixel_data(&self, x: usize, y: usize) -> &[u8; 3] {
-        static MAGENTA: [u8; 3] = [255, 0, 255];
-        if false {
-            // Debug if image doesn't load (not sure if needed)
-            return &MAGENTA;
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    pub fn mip_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// RGBA of a texel, coordinates outside the level are wrapped
+    pub fn texel(&self, level: usize, x: i64, y: i64) -> [f32; 4] {
+        let level = &self.levels[level.min(self.levels.len() - 1)];
+
+        let x = self.wrap.0.wrap(x, level.width);
+        let y = self.wrap.1.wrap(y, level.height);
+
+        level.texels[y * level.width + x]
+    }
+
+    /// Filtered RGBA at texture coordinates with v pointing up, from the full resolution level
+    pub fn sample(&self, uv: (f64, f64)) -> [f64; 4] {
+        self.sample_level(uv, 0)
+    }
+
+    /// Filtered RGBA from a single mip level
+    pub fn sample_level(&self, uv: (f64, f64), level: usize) -> [f64; 4] {
+        let level = level.min(self.levels.len() - 1);
+        let (width, height) = (self.levels[level].width, self.levels[level].height);
+
+        // Continuous texel coordinates, with texel centres at half integers
+        let x = uv.0 * width as f64 - 0.5;
+        let y = (1.0 - uv.1) * height as f64 - 0.5;
+
+        let texel = |x: i64, y: i64| self.texel(level, x, y).map(f64::from);
+
+        match self.filter {
+            ImageFilter::Nearest => texel(x.round() as i64, y.round() as i64),
+            ImageFilter::Bilinear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let weights = [
+                    (x0, y0, (1.0 - tx) * (1.0 - ty)),
+                    (x0 + 1, y0, tx * (1.0 - ty)),
+                    (x0, y0 + 1, (1.0 - tx) * ty),
+                    (x0 + 1, y0 + 1, tx * ty),
+                ];
+
+                weighted_sum(weights.iter().map(|&(x, y, w)| (texel(x, y), w)))
+            }
+            ImageFilter::Bicubic => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let wx = catmull_rom_weights(x - x0);
+                let wy = catmull_rom_weights(y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                weighted_sum((0..16).map(|i| {
+                    let (i, j) = (i % 4, i / 4);
+                    (
+                        texel(x0 + i - 1, y0 + j - 1),
+                        wx[i as usize] * wy[j as usize],
+                    )
+                }))
+            }
+        }
+    }
+
+    /// Trilinear lookup for a footprint `width` in uv units, blending the two nearest mip levels
+    pub fn sample_footprint(&self, uv: (f64, f64), width: f64) -> [f64; 4] {
+        let texels = width * self.width().max(self.height()) as f64;
+        if texels.is_nan() || texels <= 1.0 {
+            return self.sample(uv);
+        }
+
+        let level = texels.log2().min((self.levels.len() - 1) as f64);
+        let lower = level.floor() as usize;
+        let t = level - lower as f64;
+
+        let a = self.sample_level(uv, lower);
+        if t == 0.0 {
+            return a;
+        }
+        let b = self.sample_level(uv, lower + 1);
+
+        std::array::from_fn(|c| a[c] * (1.0 - t) + b[c] * t)
+    }
+}
+
+fn weighted_sum(samples: impl Iterator<Item = ([f64; 4], f64)>) -> [f64; 4] {
+    let mut sum = [0.0; 4];
+
+    for (texel, weight) in samples {
+        for c in 0..4 {
+            sum[c] += weight * texel[c];
+        }
+    }
+
+    sum
+}
+
+/// Weights of the four texels around a point `t` past the second one
+fn catmull_rom_weights(t: f64) -> [f64; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Texels with a distinct red value each, counting along rows from the top
+    fn ramp(width: usize, height: usize) -> ExtImage {
+        let texels = (0..width * height)
+            .map(|i| [i as f32, 0.0, 0.0, 1.0])
+            .collect();
+
+        ExtImage::from_texels(width, height, texels)
+    }
+
+    #[test]
+    fn wrap_modes_at_the_edges() {
+        let size = 4;
+
+        assert_eq!(WrapMode::Repeat.wrap(-1, size), 3);
+        assert_eq!(WrapMode::Repeat.wrap(4, size), 0);
+        assert_eq!(WrapMode::Repeat.wrap(-5, size), 3);
+
+        assert_eq!(WrapMode::Mirror.wrap(-1, size), 0);
+        assert_eq!(WrapMode::Mirror.wrap(4, size), 3);
+        assert_eq!(WrapMode::Mirror.wrap(-5, size), 3);
+        assert_eq!(WrapMode::Mirror.wrap(8, size), 0);
+
+        assert_eq!(WrapMode::Clamp.wrap(-1, size), 0);
+        assert_eq!(WrapMode::Clamp.wrap(4, size), 3);
+        assert_eq!(WrapMode::Clamp.wrap(100, size), 3);
+
+        for x in 0..size as i64 {
+            for mode in [WrapMode::Repeat, WrapMode::Mirror, WrapMode::Clamp] {
+                assert_eq!(mode.wrap(x, size), x as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn texels_wrap_per_axis() {
+        let image = ramp(4, 3).with_wrap_uv(WrapMode::Repeat, WrapMode::Clamp);
+
+        assert_eq!(image.texel(0, -1, 0)[0], 3.0);
+        assert_eq!(image.texel(0, 4, 1)[0], 4.0);
+        assert_eq!(image.texel(0, 0, -1)[0], 0.0);
+        assert_eq!(image.texel(0, 1, 3)[0], 9.0);
+    }
+
+    #[test]
+    fn mip_pyramid_keeps_odd_edges() {
+        let image = ramp(5, 3);
+        let sizes: Vec<_> = image
+            .levels
+            .iter()
+            .map(|level| (level.width, level.height))
+            .collect();
+
+        assert_eq!(sizes, [(5, 3), (3, 2), (2, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn mip_levels_keep_the_mean() {
+        let image = ramp(8, 4);
+        let mean = |level: &MipLevel| {
+            level.texels.iter().map(|t| t[0] as f64).sum::<f64>() / level.texels.len() as f64
+        };
+
+        for level in &image.levels {
+            assert!((mean(level) - 15.5).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn texel_centres_sample_exactly() {
+        for filter in [
+            ImageFilter::Nearest,
+            ImageFilter::Bilinear,
+            ImageFilter::Bicubic,
+        ] {
+            let image = ramp(4, 3).with_filter(filter);
+
+            for y in 0..3 {
+                for x in 0..4 {
+                    let uv = ((x as f64 + 0.5) / 4.0, 1.0 - (y as f64 + 0.5) / 3.0);
+                    let sample = image.sample(uv);
+
+                    assert!((sample[0] - (y * 4 + x) as f64).abs() < 1e-9);
+                }
+            }
         }
+    }
+
+    #[test]
+    fn constant_images_sample_constant() {
+        let texels = vec![[0.25, 0.5, 0.75, 1.0]; 6 * 5];
 
-        let x = x.clamp(0, self.width) as u32;
-        let y = y.clamp(0, self.height) as u32;
+        for filter in [
+            ImageFilter::Nearest,
+            ImageFilter::Bilinear,
+            ImageFilter::Bicubic,
+        ] {
+            let image = ExtImage::from_texels(6, 5, texels.clone())
+                .with_filter(filter)
+                .with_wrap(WrapMode::Mirror);
 
-        &self.image.get_pixel(x, y).0
+            for width in [0.0, 0.1, 0.3, 2.0, f64::NAN] {
+                let sample = image.sample_footprint((0.37, 0.91), width);
+
+                for (c, expected) in [0.25, 0.5, 0.75, 1.0].into_iter().enumerate() {
+                    assert!((sample[c] - expected).abs() < 1e-6);
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,333 @@
+use std::{fs, path::Path};
+
+use crate::{ray::Ray, vec3::Vec3};
+
+/// Lens prescriptions are written in millimetres, the scene is in metres
+const MILLIMETRES: f64 = 0.001;
+
+#[derive(Clone)]
+pub struct LensElement {
+    // Zero for the aperture stop
+    pub curvature_radius: f64,
+    // Distance along the optical axis to the next element (or the film)
+    pub thickness: f64,
+    // Zero for the aperture stop, otherwise ior of the medium behind the interface
+    pub refraction_index: f64,
+    pub aperture_radius: f64,
+}
+
+/// A stack of spherical lens elements, ordered from the scene side to the film side
+///
+/// Lens space has the film at z = 0, with the elements and the scene along negative z
+#[derive(Clone)]
+pub struct LensSystem {
+    elements: Vec<LensElement>,
+}
+
+impl LensSystem {
+    pub fn new(elements: Vec<LensElement>) -> Result<Self, String> {
+        if elements.is_empty() {
+            return Err("Lens system has no elements".to_string());
+        }
+
+        Ok(Self { elements })
+    }
+
+    /// Loads a prescription with one element per line: `radius thickness ior aperture_diameter`
+    ///
+    /// Values are in millimetres. Blank lines and lines starting with `#` are skipped.
+    pub fn load(file_name: &str) -> Result<Self, String> {
+        let path = Path::new(file_name);
+
+        if !path.is_file() {
+            return Err(format!("File `{}` not found", file_name));
+        }
+
+        match fs::read_to_string(path) {
+            Ok(prescription) => Self::parse(&prescription),
+            Err(_) => Err(format!("Could not load `{}`", file_name)),
+        }
+    }
+
+    pub fn parse(prescription: &str) -> Result<Self, String> {
+        let mut elements = Vec::new();
+
+        for (line_number, line) in prescription.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(|value| value.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Line {}: {}", line_number + 1, e))?;
+
+            if values.len() != 4 {
+                return Err(format!(
+                    "Line {}: expected 4 values, found {}",
+                    line_number + 1,
+                    values.len()
+                ));
+            }
+
+            elements.push(LensElement {
+                curvature_radius: values[0] * MILLIMETRES,
+                thickness: values[1] * MILLIMETRES,
+                refraction_index: values[2],
+                aperture_radius: values[3] * MILLIMETRES / 2.0,
+            });
+        }
+
+        Self::new(elements)
+    }
+
+    pub fn rear_z(&self) -> f64 {
+        self.elements.last().unwrap().thickness
+    }
+
+    pub fn front_z(&self) -> f64 {
+        self.elements.iter().map(|element| element.thickness).sum()
+    }
+
+    pub fn rear_aperture_radius(&self) -> f64 {
+        self.elements.last().unwrap().aperture_radius
+    }
+
+    /// Moves the film so that objects at `focus_distance` in front of it are sharp
+    pub fn focus(&mut self, focus_distance: f64) -> Result<(), String> {
+        let (principal_planes, focal_points) = self.thick_lens_approximation()?;
+
+        // Solve the thick lens equation for the film offset
+        let focal_length = focal_points[0] - principal_planes[0];
+        let z = -focus_distance;
+        let c = (principal_planes[1] - z - principal_planes[0])
+            * (principal_planes[1] - z - 4.0 * focal_length - principal_planes[0]);
+
+        if c <= 0.0 {
+            return Err(format!(
+                "Cannot focus at {} with a focal length of {}",
+                focus_distance, focal_length
+            ));
+        }
+
+        let delta = 0.5 * (principal_planes[1] - z + principal_planes[0] - c.sqrt());
+
+        self.elements.last_mut().unwrap().thickness += delta;
+
+        Ok(())
+    }
+
+    fn thick_lens_approximation(&self) -> Result<([f64; 2], [f64; 2]), String> {
+        // Paraxial rays, parallel to the optical axis
+        let height = 0.001
+            * self
+                .rear_aperture_radius()
+                .max(self.elements[0].aperture_radius);
+
+        let scene_ray = Ray::new(
+            Vec3::new(height, 0.0, -(self.front_z() + 1.0)),
+            Vec3::new(0.0, 0.0, 1.0),
+            0.0,
+        );
+        let film_ray = self
+            .trace_from_scene(&scene_ray)
+            .ok_or("Paraxial ray from the scene did not reach the film")?;
+        let (principal_plane_0, focal_point_0) = Self::cardinal_points(&scene_ray, &film_ray);
+
+        let film_ray = Ray::new(
+            Vec3::new(height, 0.0, 1.0 - self.rear_z()),
+            Vec3::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let scene_ray = self
+            .trace_from_film(&film_ray)
+            .ok_or("Paraxial ray from the film did not reach the scene")?;
+        let (principal_plane_1, focal_point_1) = Self::cardinal_points(&film_ray, &scene_ray);
+
+        Ok((
+            [principal_plane_0, principal_plane_1],
+            [focal_point_0, focal_point_1],
+        ))
+    }
+
+    /// Finds the z of the principal plane and focal point from a paraxial ray before and after the lens
+    fn cardinal_points(ray_in: &Ray, ray_out: &Ray) -> (f64, f64) {
+        let t_focus = -ray_out.origin.x / ray_out.direction.x;
+        let focal_point = ray_out.at(t_focus).z;
+
+        let t_principal = (ray_in.origin.x - ray_out.origin.x) / ray_out.direction.x;
+        let principal_plane = ray_out.at(t_principal).z;
+
+        (principal_plane, focal_point)
+    }
+
+    /// Traces a lens space ray leaving the film out through the front element
+    pub fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut origin = ray.origin;
+        let mut direction = ray.direction;
+        let mut element_z = 0.0;
+
+        for i in (0..self.elements.len()).rev() {
+            let element = &self.elements[i];
+            element_z -= element.thickness;
+
+            origin = Self::intersect_element(element, element_z, origin, direction)?;
+
+            if element.curvature_radius != 0.0 {
+                let eta_i = self.medium_index(i);
+                let eta_t = if i > 0 { self.medium_index(i - 1) } else { 1.0 };
+
+                direction =
+                    Self::refract_at_element(element, element_z, origin, direction, eta_i / eta_t)?;
+            }
+        }
+
+        Some(Ray::new(origin, direction, ray.time))
+    }
+
+    /// Traces a lens space ray entering the front element back to the film
+    pub fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut origin = ray.origin;
+        let mut direction = ray.direction;
+        let mut element_z = -self.front_z();
+
+        for (i, element) in self.elements.iter().enumerate() {
+            origin = Self::intersect_element(element, element_z, origin, direction)?;
+
+            if element.curvature_radius != 0.0 {
+                let eta_i = if i > 0 { self.medium_index(i - 1) } else { 1.0 };
+                let eta_t = self.medium_index(i);
+
+                direction =
+                    Self::refract_at_element(element, element_z, origin, direction, eta_i / eta_t)?;
+            }
+
+            element_z += element.thickness;
+        }
+
+        Some(Ray::new(origin, direction, ray.time))
+    }
+
+    fn medium_index(&self, i: usize) -> f64 {
+        let refraction_index = self.elements[i].refraction_index;
+
+        if refraction_index == 0.0 {
+            1.0
+        } else {
+            refraction_index
+        }
+    }
+
+    /// Returns the point where the ray crosses the element, if it passes within the aperture
+    fn intersect_element(
+        element: &LensElement,
+        element_z: f64,
+        origin: Vec3,
+        direction: Vec3,
+    ) -> Option<Vec3> {
+        let t = if element.curvature_radius == 0.0 {
+            // Aperture stop is a flat disk
+            let t = (element_z - origin.z) / direction.z;
+            if !t.is_finite() || t < 0.0 {
+                return None;
+            }
+            t
+        } else {
+            let center = Vec3::new(0.0, 0.0, element_z + element.curvature_radius);
+            let oc = origin - center;
+
+            let a = direction.length_squared();
+            let h = direction.dot(&oc);
+            let c = oc.length_squared() - element.curvature_radius * element.curvature_radius;
+
+            let discriminant = h * h - a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+
+            let discriminant_sqrt = discriminant.sqrt();
+            let t_near = (-h - discriminant_sqrt) / a;
+            let t_far = (-h + discriminant_sqrt) / a;
+
+            // Pick the side of the sphere that forms the lens surface
+            let use_near = (direction.z > 0.0) ^ (element.curvature_radius < 0.0);
+            let t = if use_near { t_near } else { t_far };
+            if t < 0.0 {
+                return None;
+            }
+            t
+        };
+
+        let hit = origin + t * direction;
+        if hit.x * hit.x + hit.y * hit.y > element.aperture_radius * element.aperture_radius {
+            return None;
+        }
+
+        Some(hit)
+    }
+
+    /// Refracts through a spherical element, returning `None` on total internal reflection
+    fn refract_at_element(
+        element: &LensElement,
+        element_z: f64,
+        hit: Vec3,
+        direction: Vec3,
+        eta: f64,
+    ) -> Option<Vec3> {
+        let center = Vec3::new(0.0, 0.0, element_z + element.curvature_radius);
+        let unit_direction = direction.normalize();
+
+        let mut normal = (hit - center).normalize();
+        if normal.dot(&unit_direction) > 0.0 {
+            normal = -normal;
+        }
+
+        let cos_i = (-unit_direction).dot(&normal);
+        let sin_2_t = eta * eta * (1.0 - cos_i * cos_i).max(0.0);
+        if sin_2_t >= 1.0 {
+            return None;
+        }
+
+        let cos_t = (1.0 - sin_2_t).sqrt();
+
+        Some(eta * unit_direction + (eta * cos_i - cos_t) * normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// z where a ray leaving the lens towards the film crosses the optical axis
+    fn axis_crossing(lens: &LensSystem, object_distance: f64, height: f64) -> f64 {
+        let front = Vec3::new(height, 0.0, -lens.front_z());
+        let object = Vec3::new(0.0, 0.0, -object_distance);
+        let film_ray = lens
+            .trace_from_scene(&Ray::new(object, front - object, 0.0))
+            .unwrap();
+
+        film_ray.at(-film_ray.origin.x / film_ray.direction.x).z
+    }
+
+    #[test]
+    fn focus_images_the_focus_distance_on_the_film() {
+        let mut lens = LensSystem::load("lenses/dgauss.50mm.dat").unwrap();
+
+        for focus_distance in [0.5, 2.0, 10.0] {
+            lens.focus(focus_distance).unwrap();
+
+            let crossing = axis_crossing(&lens, focus_distance, 1e-4);
+            assert!(
+                crossing.abs() < 1e-5,
+                "focused {} m away from the film",
+                crossing
+            );
+
+            // Nearer objects come to a focus behind the film
+            // Nearer objects come to a focus behind the film
+            assert!(axis_crossing(&lens, 0.5 * focus_distance, 1e-4) > 1e-4);
+        }
+    }
+}
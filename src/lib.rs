@@ -1,10 +1,21 @@
+pub mod animation;
+pub mod background;
 pub mod camera;
+pub mod cancellation;
+pub mod complex;
+pub mod distribution;
+pub mod fresnel;
 pub mod hittable;
 pub mod image;
 pub mod interval;
+pub mod lens;
 pub mod material;
+pub mod microfacet;
+pub mod onb;
 pub mod perlin;
+pub mod progress;
 pub mod random;
 pub mod ray;
+pub mod spectrum;
 pub mod texture;
 pub mod vec3;
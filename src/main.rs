@@ -3,16 +3,20 @@ use std::{sync::Arc, time::Instant};
 use nanorand::{tls_rng, Rng};
 use raytracer::{
     camera::Camera,
+    cancellation::RenderLimits,
     hittable::{
         bvh::BVHNode,
         constant_medium::ConstantMedium,
         quad::{quad_box, Quad},
         sphere::Sphere,
+        subsurface::Subsurface,
         transform::{RotateY, Translate},
         Hittable, HittableList,
     },
     image::color::Color,
-    material::Material,
+    lens::LensSystem,
+    material::{dispersion::Dispersion, principled::Principled, Material},
+    progress::TerminalProgress,
     random::gen_range_f64,
     texture::Texture,
     vec3::Vec3,
@@ -88,18 +92,19 @@ fn weekend_1() -> (HittableList, Camera) {
 
     (
         world.with_bvh(),
-        Camera::new(
-            ASPECT_RATIO,
-            1200,
-            20.0,
-            Vec3::new(13.0, 2.0, 3.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            0.6,
-            10.0,
-            SKY_COLOUR,
-            500,
-            50,
-        ),
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(1200)
+            .vfov(20.0)
+            .look_from(Vec3::new(13.0, 2.0, 3.0))
+            .look_at(Vec3::new(0.0, 0.0, 0.0))
+            .defocus_angle(0.6)
+            .focus_dist(10.0)
+            .background(SKY_COLOUR)
+            .samples_per_pixel(500)
+            .max_depth(50)
+            .build()
+            .unwrap(),
     )
 }
 
@@ -174,18 +179,19 @@ fn bouncing_spheres() -> (HittableList, Camera) {
 
     (
         world.with_bvh(),
-        Camera::new(
-            ASPECT_RATIO,
-            IMAGE_WIDTH,
-            VFOV,
-            Vec3::new(13.0, 2.0, 3.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            0.6,
-            10.0,
-            SKY_COLOUR,
-            SAMPLES_PER_PIXEL,
-            MAX_DEPTH,
-        ),
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .vfov(VFOV)
+            .look_from(Vec3::new(13.0, 2.0, 3.0))
+            .look_at(Vec3::new(0.0, 0.0, 0.0))
+            .defocus_angle(0.6)
+            .focus_dist(10.0)
+            .background(SKY_COLOUR)
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            .max_depth(MAX_DEPTH)
+            .build()
+            .unwrap(),
     )
 }
 
@@ -214,18 +220,19 @@ fn checkered_spheres() -> (HittableList, Camera) {
 
     (
         world,
-        Camera::new(
-            ASPECT_RATIO,
-            IMAGE_WIDTH,
-            VFOV,
-            Vec3::new(13.0, 2.0, 3.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            0.0,
-            1.0,
-            SKY_COLOUR,
-            SAMPLES_PER_PIXEL,
-            MAX_DEPTH,
-        ),
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .vfov(VFOV)
+            .look_from(Vec3::new(13.0, 2.0, 3.0))
+            .look_at(Vec3::new(0.0, 0.0, 0.0))
+            .defocus_angle(0.0)
+            .focus_dist(1.0)
+            .background(SKY_COLOUR)
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            .max_depth(MAX_DEPTH)
+            .build()
+            .unwrap(),
     )
 }
 
@@ -243,18 +250,19 @@ fn earth() -> (HittableList, Camera) {
 
     (
         world,
-        Camera::new(
-            ASPECT_RATIO,
-            IMAGE_WIDTH,
-            VFOV,
-            Vec3::new(0.0, 0.0, 12.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            0.0,
-            10.0,
-            SKY_COLOUR,
-            SAMPLES_PER_PIXEL,
-            MAX_DEPTH,
-        ),
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .vfov(VFOV)
+            .look_from(Vec3::new(0.0, 0.0, 12.0))
+            .look_at(Vec3::new(0.0, 0.0, 0.0))
+            .defocus_angle(0.0)
+            .focus_dist(10.0)
+            .background(SKY_COLOUR)
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            .max_depth(MAX_DEPTH)
+            .build()
+            .unwrap(),
     )
 }
 
@@ -278,18 +286,19 @@ fn perlin_spheres() -> (HittableList, Camera) {
 
     (
         world,
-        Camera::new(
-            ASPECT_RATIO,
-            IMAGE_WIDTH,
-            VFOV,
-            Vec3::new(13.0, 2.0, 3.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            0.0,
-            10.0,
-            SKY_COLOUR,
-            SAMPLES_PER_PIXEL,
-            MAX_DEPTH,
-        ),
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .vfov(VFOV)
+            .look_from(Vec3::new(13.0, 2.0, 3.0))
+            .look_at(Vec3::new(0.0, 0.0, 0.0))
+            .defocus_angle(0.0)
+            .focus_dist(10.0)
+            .background(SKY_COLOUR)
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            .max_depth(MAX_DEPTH)
+            .build()
+            .unwrap(),
     )
 }
 
@@ -339,18 +348,19 @@ fn quads() -> (HittableList, Camera) {
 
     (
         world,
-        Camera::new(
-            ASPECT_RATIO,
-            IMAGE_WIDTH,
-            80.0,
-            Vec3::new(0.0, 0.0, 9.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            0.0,
-            1.0,
-            SKY_COLOUR,
-            SAMPLES_PER_PIXEL,
-            MAX_DEPTH,
-        ),
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .vfov(80.0)
+            .look_from(Vec3::new(0.0, 0.0, 9.0))
+            .look_at(Vec3::new(0.0, 0.0, 0.0))
+            .defocus_angle(0.0)
+            .focus_dist(1.0)
+            .background(SKY_COLOUR)
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            .max_depth(MAX_DEPTH)
+            .build()
+            .unwrap(),
     )
 }
 
@@ -390,18 +400,19 @@ fn simple_light() -> (HittableList, Camera) {
 
     (
         world,
-        Camera::new(
-            ASPECT_RATIO,
-            IMAGE_WIDTH,
-            VFOV,
-            Vec3::new(26.0, 3.0, 6.0),
-            Vec3::new(0.0, 2.0, 0.0),
-            0.0,
-            1.0,
-            Color::new(0.0, 0.0, 0.0),
-            SAMPLES_PER_PIXEL,
-            MAX_DEPTH,
-        ),
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .vfov(VFOV)
+            .look_from(Vec3::new(26.0, 3.0, 6.0))
+            .look_at(Vec3::new(0.0, 2.0, 0.0))
+            .defocus_angle(0.0)
+            .focus_dist(1.0)
+            .background(Color::new(0.0, 0.0, 0.0))
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            .max_depth(MAX_DEPTH)
+            .build()
+            .unwrap(),
     )
 }
 
@@ -491,18 +502,19 @@ fn cornell_box() -> (HittableList, Camera) {
 
     (
         world.with_bvh(),
-        Camera::new(
-            1.0,
-            800,
-            40.0,
-            Vec3::new(278.0, 278.0, -800.0),
-            Vec3::new(278.0, 278.0, 0.0),
-            0.0,
-            1.0,
-            Color::new(0.0, 0.0, 0.0),
-            800,
-            100,
-        ),
+        Camera::builder()
+            .aspect_ratio(1.0)
+            .image_width(800)
+            .vfov(40.0)
+            .look_from(Vec3::new(278.0, 278.0, -800.0))
+            .look_at(Vec3::new(278.0, 278.0, 0.0))
+            .defocus_angle(0.0)
+            .focus_dist(1.0)
+            .background(Color::new(0.0, 0.0, 0.0))
+            .samples_per_pixel(800)
+            .max_depth(100)
+            .build()
+            .unwrap(),
     )
 }
 
@@ -634,18 +646,288 @@ fn final_scene() -> (HittableList, Camera) {
 
     (
         world,
-        Camera::new(
-            1.0,
-            800,
-            40.0,
-            Vec3::new(478.0, 278.0, -600.0),
-            Vec3::new(278.0, 278.0, 0.0),
-            0.0,
+        Camera::builder()
+            .aspect_ratio(1.0)
+            .image_width(800)
+            .vfov(40.0)
+            .look_from(Vec3::new(478.0, 278.0, -600.0))
+            .look_at(Vec3::new(278.0, 278.0, 0.0))
+            .defocus_angle(0.0)
+            .focus_dist(1.0)
+            .background(Color::new(0.0, 0.0, 0.0))
+            .samples_per_pixel(2048)
+            .max_depth(50)
+            .build()
+            .unwrap(),
+    )
+}
+
+fn double_gauss_lens() -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let checker = Arc::new(Texture::checker_from_colors(
+        0.5,
+        Color::new(0.2, 0.3, 0.1),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::still(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Material::lambertian(checker)),
+    )));
+
+    // A row of spheres receding from the camera, only the middle one is in focus
+    let colors = [
+        Color::new(0.8, 0.2, 0.2),
+        Color::new(0.2, 0.8, 0.2),
+        Color::new(0.2, 0.2, 0.8),
+    ];
+    for (i, color) in colors.into_iter().enumerate() {
+        world.add(Arc::new(Sphere::still(
+            Vec3::new(i as f64 - 1.0, 0.5, -3.0 - 3.0 * i as f64),
+            0.5,
+            Arc::new(Material::lambertian_from_color(color)),
+        )));
+    }
+
+    let lens = LensSystem::load("lenses/dgauss.50mm.dat").unwrap();
+
+    (
+        world,
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .look_from(Vec3::new(0.0, 1.0, 2.0))
+            .look_at(Vec3::new(0.0, 0.5, -6.0))
+            .focus_dist(8.0)
+            .background(SKY_COLOUR)
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            .max_depth(MAX_DEPTH)
+            .lens(lens, 35.0)
+            .build()
+            .unwrap(),
+    )
+}
+
+fn metals() -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let checker = Arc::new(Texture::checker_from_colors(
+        0.5,
+        Color::new(0.2, 0.2, 0.2),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::still(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Material::lambertian(checker)),
+    )));
+
+    // Columns of conductor presets, getting rougher towards the back
+    let presets: [fn(f64) -> Material; 4] = [
+        Material::gold,
+        Material::copper,
+        Material::aluminium,
+        Material::silver,
+    ];
+    for (i, preset) in presets.iter().enumerate() {
+        for (j, roughness) in [0.0, 0.2, 0.5].into_iter().enumerate() {
+            world.add(Arc::new(Sphere::still(
+                Vec3::new(2.2 * i as f64 - 3.3, 1.0, -3.0 * j as f64),
+                1.0,
+                Arc::new(preset(roughness)),
+            )));
+        }
+    }
+
+    (
+        world.with_bvh(),
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .vfov(VFOV)
+            .look_from(Vec3::new(0.0, 6.0, 20.0))
+            .look_at(Vec3::new(0.0, 1.0, -2.0))
+            .background(SKY_COLOUR)
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            .max_depth(MAX_DEPTH)
+            .build()
+            .unwrap(),
+    )
+}
+
+fn principled() -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let checker = Arc::new(Texture::checker_from_colors(
+        0.5,
+        Color::new(0.2, 0.2, 0.2),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::still(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Material::lambertian(checker)),
+    )));
+
+    let scalar = |value| Arc::new(Texture::scalar(value));
+
+    let materials = [
+        // Plastic
+        Principled::new()
+            .base_color(Arc::new(Texture::from_rgb(0.8, 0.1, 0.1)))
+            .roughness(scalar(0.3)),
+        // Car paint
+        Principled::new()
+            .base_color(Arc::new(Texture::from_rgb(0.05, 0.1, 0.5)))
+            .roughness(scalar(0.6))
+            .clearcoat(scalar(1.0)),
+        // Brushed gold
+        Principled::new()
+            .base_color(Arc::new(Texture::from_rgb(1.0, 0.77, 0.34)))
+            .metallic(scalar(1.0))
+            .roughness(scalar(0.35)),
+        // Frosted glass
+        Principled::new()
+            .base_color(Arc::new(Texture::from_rgb(0.8, 1.0, 0.9)))
+            .transmission(scalar(1.0))
+            .roughness(scalar(0.15)),
+        // Velvet
+        Principled::new()
+            .base_color(Arc::new(Texture::from_rgb(0.3, 0.05, 0.3)))
+            .roughness(scalar(1.0))
+            .specular(scalar(0.0))
+            .sheen(Arc::new(Texture::from_rgb(0.6, 0.4, 0.6))),
+        // Glowing
+        Principled::new()
+            .base_color(scalar(0.2))
+            .emission(Arc::new(Texture::from_rgb(4.0, 2.0, 0.5))),
+    ];
+
+    for (i, principled) in materials.into_iter().enumerate() {
+        world.add(Arc::new(Sphere::still(
+            Vec3::new(2.2 * i as f64 - 5.5, 1.0, 0.0),
             1.0,
-            Color::new(0.0, 0.0, 0.0),
-            2048,
-            50,
+            Arc::new(Material::principled(principled)),
+        )));
+    }
+
+    (
+        world.with_bvh(),
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .vfov(VFOV)
+            .look_from(Vec3::new(0.0, 5.0, 30.0))
+            .look_at(Vec3::new(0.0, 1.0, 0.0))
+            .background(SKY_COLOUR)
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            .max_depth(MAX_DEPTH)
+            .build()
+            .unwrap(),
+    )
+}
+
+fn dispersion() -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    // Backlit checker board to show the colour fringes
+    let checker = Arc::new(Texture::checker_from_colors(
+        0.25,
+        Color::black(),
+        Color::white(),
+    ));
+    world.add(Arc::new(Quad::new(
+        Vec3::new(-8.0, -4.0, -4.0),
+        Vec3::new(16.0, 0.0, 0.0),
+        Vec3::new(0.0, 8.0, 0.0),
+        Arc::new(Material::diffuse_light(checker)),
+    )));
+
+    world.add(Arc::new(Sphere::still(
+        Vec3::new(-1.5, 0.0, 0.0),
+        1.3,
+        Arc::new(Material::dielectric_dispersive(Dispersion::bk7())),
+    )));
+    world.add(Arc::new(Sphere::still(
+        Vec3::new(1.5, 0.0, 0.0),
+        1.3,
+        Arc::new(Material::dielectric_dispersive(Dispersion::diamond())),
+    )));
+
+    (
+        world,
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .vfov(40.0)
+            .look_from(Vec3::new(0.0, 0.0, 8.0))
+            .look_at(Vec3::zero())
+            .background(Color::black())
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            .max_depth(MAX_DEPTH)
+            .build()
+            .unwrap(),
+    )
+}
+
+fn subsurface() -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    world.add(Arc::new(Sphere::still(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Material::lambertian_from_color(Color::new(0.5, 0.5, 0.5))),
+    )));
+    world.add(Arc::new(Quad::new(
+        Vec3::new(-2.0, 5.0, -2.0),
+        Vec3::new(4.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 4.0),
+        Arc::new(Material::diffuse_light_from_color(Color::new(
+            4.0, 4.0, 4.0,
+        ))),
+    )));
+
+    // Wax, skin and marble
+    let media = [
+        (Color::new(0.99, 0.95, 0.8), Color::new(0.3, 0.3, 0.3), 1.45),
+        (Color::new(0.99, 0.9, 0.8), Color::new(0.4, 0.15, 0.08), 1.4),
+        (
+            Color::new(0.999, 0.999, 0.999),
+            Color::new(0.05, 0.05, 0.05),
+            1.5,
         ),
+    ];
+
+    for (i, (albedo, mean_free_path, refraction_index)) in media.into_iter().enumerate() {
+        let boundary = Arc::new(Sphere::still(
+            Vec3::new(2.2 * i as f64 - 2.2, 1.0, 0.0),
+            1.0,
+            Arc::new(Material::lambertian_from_color(Color::white())),
+        ));
+
+        world.add(Arc::new(Subsurface::new(
+            boundary,
+            albedo,
+            mean_free_path,
+            refraction_index,
+        )));
+    }
+
+    (
+        world,
+        Camera::builder()
+            .aspect_ratio(ASPECT_RATIO)
+            .image_width(IMAGE_WIDTH)
+            .vfov(VFOV)
+            .look_from(Vec3::new(0.0, 3.0, 20.0))
+            .look_at(Vec3::new(0.0, 1.0, 0.0))
+            .background(Color::new(0.05, 0.05, 0.08))
+            .samples_per_pixel(SAMPLES_PER_PIXEL)
+            // Random walks bounce many times inside the media
+            .max_depth(1000)
+            .build()
+            .unwrap(),
     )
 }
 
@@ -660,12 +942,20 @@ fn main() {
         6 => simple_light(),
         7 => cornell_box(),
         8 => final_scene(),
+        9 => double_gauss_lens(),
+        10 => metals(),
+        11 => principled(),
+        12 => dispersion(),
+        13 => subsurface(),
         _ => todo!(),
     };
 
     let now = Instant::now();
-    // camera.render_single_threaded(&world).save("test");
-    camera.render(&world).save("test");
+    // camera.render_single_threaded(&world, &TerminalProgress::new()).save("test");
+    camera
+        .render(&world, &TerminalProgress::new(), &RenderLimits::none())
+        .image
+        .save("test");
     let elapsed_time = now.elapsed();
 
     println!("Rendering took {:.2} seconds.", elapsed_time.as_secs_f64());
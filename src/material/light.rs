@@ -0,0 +1,57 @@
+use std::f64::consts::PI;
+
+/// Luminous efficacy of 555 nm light, in lumens per watt
+const LUMINOUS_EFFICACY: f64 = 683.0;
+
+/// Strength of a diffuse emitter in physical units
+///
+/// Scene radiance is taken to be in W/(sr m²) of ideal 555 nm light, with scene units as metres.
+#[derive(Debug, Clone, Copy)]
+pub enum LightUnit {
+    /// Total radiant power leaving the surface
+    Watts(f64),
+    /// Total luminous flux leaving the surface
+    Lumens(f64),
+    /// Luminance, in cd/m², which does not depend on the area
+    Nits(f64),
+}
+
+impl LightUnit {
+    /// Radiance of a Lambertian emitter with this output spread over `area`
+    ///
+    /// Power spread over no area gives no radiance rather than an infinite one.
+    pub fn radiance(&self, area: f64) -> f64 {
+        match self {
+            LightUnit::Watts(_) | LightUnit::Lumens(_) if area <= 0.0 => 0.0,
+            LightUnit::Watts(watts) => watts / (PI * area),
+            LightUnit::Lumens(lumens) => lumens / (LUMINOUS_EFFICACY * PI * area),
+            LightUnit::Nits(nits) => nits / LUMINOUS_EFFICACY,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_is_kept_over_the_area() {
+        for area in [0.01, 1.0, 25.0] {
+            // Lambertian emitters send π times their radiance per unit area
+            let watts = LightUnit::Watts(100.0).radiance(area) * PI * area;
+            let lumens = LightUnit::Lumens(683.0).radiance(area) * PI * area;
+
+            assert!((watts - 100.0).abs() < 1e-9);
+            assert!((lumens - 1.0).abs() < 1e-9);
+            assert_eq!(LightUnit::Nits(683.0).radiance(area), 1.0);
+        }
+    }
+
+    #[test]
+    fn degenerate_areas_give_no_light() {
+        for area in [0.0, -1.0] {
+            assert_eq!(LightUnit::Watts(100.0).radiance(area), 0.0);
+            assert_eq!(LightUnit::Lumens(100.0).radiance(area), 0.0);
+        }
+    }
+}
@@ -0,0 +1,1270 @@
+pub mod dispersion;
+pub mod light;
+pub mod normal_map;
+pub mod principled;
+pub mod sheen;
+pub mod thin_film;
+
+use std::{f64::consts::PI, sync::Arc};
+
+use nanorand::{tls_rng, Rng};
+
+use crate::{
+    complex::Complex,
+    fresnel,
+    hittable::HitRecord,
+    image::color::Color,
+    microfacet::TrowbridgeReitz,
+    onb::OrthonormalBasis,
+    random::gen_f64,
+    ray::{Ray, RayDifferentials},
+    spectrum,
+    texture::Texture,
+    vec3::Vec3,
+};
+
+use dispersion::Dispersion;
+use light::LightUnit;
+use normal_map::NormalMap;
+use principled::Principled;
+use sheen::Sheen;
+use thin_film::ThinFilm;
+
+#[derive(Clone)]
+pub enum Material {
+    Dielectric {
+        albedo: Color,
+        // Ratio of material's ior over ior of emclosing media
+        refraction_index: f64,
+        // Beer-Lambert absorption coefficient per unit length inside the material
+        absorption: Color,
+        // Replaces refraction_index for rays carrying a wavelength
+        dispersion: Option<Dispersion>,
+        thin_film: Option<ThinFilm>,
+    },
+    RoughDielectric {
+        albedo: Color,
+        refraction_index: f64,
+        // Perceptual roughness, taken from the texture's luminance
+        roughness: Arc<Texture>,
+    },
+    DiffuseLight {
+        texture: Arc<Texture>,
+        // Otherwise only the side the normal points to emits
+        two_sided: bool,
+    },
+    Lambertian {
+        texture: Arc<Texture>,
+    },
+    Metal {
+        // Complex ior of the conductor per color channel
+        eta: Color,
+        k: Color,
+        roughness: f64,
+        thin_film: Option<ThinFilm>,
+    },
+    AnisotropicMetal {
+        eta: Color,
+        k: Color,
+        // Perceptual roughness along and across the tangent, from the textures' luminance
+        roughness_u: Arc<Texture>,
+        roughness_v: Arc<Texture>,
+        // Turns of the tangent around the normal, one being a full turn
+        rotation: Arc<Texture>,
+    },
+    Isotropic {
+        texture: Arc<Texture>,
+    },
+    Principled(Principled),
+    Mix {
+        first: Arc<Material>,
+        second: Arc<Material>,
+        // Chance of using the second material, from the texture's luminance
+        mask: Arc<Texture>,
+    },
+    // Boundary and scattering events of a random walk medium, traced in a single channel or wavelength
+    Subsurface {
+        extinction: Color,
+        refraction_index: f64,
+    },
+    SubsurfacePhase {
+        albedo: Arc<Texture>,
+    },
+    Coated {
+        base: Arc<Material>,
+        // Dielectric coat over the base
+        refraction_index: f64,
+        roughness: Arc<Texture>,
+    },
+    // Diffuse fabric with a sheen lobe from its fibres
+    Cloth {
+        albedo: Arc<Texture>,
+        sheen: Sheen,
+    },
+    // Alpha tested by the hittables, so cut out hits count as misses
+    Cutout {
+        base: Arc<Material>,
+        opacity: Arc<Texture>,
+    },
+    // Adds emission to the base, which still scatters as before
+    Emissive {
+        base: Arc<Material>,
+        emission: Arc<Texture>,
+        strength: f64,
+        two_sided: bool,
+    },
+    // Scatters off the base with a shading normal perturbed by the map
+    NormalMapped {
+        base: Arc<Material>,
+        map: NormalMap,
+    },
+}
+
+// Constructors
+impl Material {
+    pub fn dielectric(refraction_index: f64) -> Self {
+        Self::dielectric_absorbing(refraction_index, Color::black())
+    }
+
+    pub fn dielectric_absorbing(refraction_index: f64, absorption: Color) -> Self {
+        Self::Dielectric {
+            albedo: Color::white(),
+            refraction_index,
+            absorption,
+            dispersion: None,
+            thin_film: None,
+        }
+    }
+
+    /// Dielectric whose ior depends on wavelength, which makes the renderer trace it spectrally
+    pub fn dielectric_dispersive(dispersion: Dispersion) -> Self {
+        Self::Dielectric {
+            albedo: Color::white(),
+            // Index at the Fraunhofer d line for rays without a wavelength
+            refraction_index: dispersion.refraction_index(587.6),
+            absorption: Color::black(),
+            dispersion: Some(dispersion),
+            thin_film: None,
+        }
+    }
+
+    /// Absorbing dielectric that lets through `color` after travelling `distance` inside it
+    pub fn dielectric_tinted(refraction_index: f64, color: Color, distance: f64) -> Self {
+        let absorption = |transmittance: f64| -transmittance.max(1e-6).ln() / distance;
+
+        Self::dielectric_absorbing(
+            refraction_index,
+            Color::new(
+                absorption(color.r),
+                absorption(color.g),
+                absorption(color.b),
+            ),
+        )
+    }
+
+    pub fn rough_dielectric(refraction_index: f64, roughness: f64) -> Self {
+        Self::rough_dielectric_textured(refraction_index, Arc::new(Texture::scalar(roughness)))
+    }
+
+    pub fn rough_dielectric_textured(refraction_index: f64, roughness: Arc<Texture>) -> Self {
+        Self::RoughDielectric {
+            albedo: Color::white(),
+            refraction_index,
+            roughness,
+        }
+    }
+
+    pub fn diffuse_light(texture: Arc<Texture>) -> Self {
+        Self::DiffuseLight {
+            texture,
+            two_sided: true,
+        }
+    }
+
+    pub fn diffuse_light_from_color(albedo: Color) -> Self {
+        Self::diffuse_light(Arc::new(Texture::color(albedo)))
+    }
+
+    /// Light of the given colour and physical strength, `area` being the emitting surface's area
+    ///
+    /// The colour only sets the hue, it is rescaled to a luminance of one and black gives no light.
+    /// The light emits from its front face only, making it two-sided doubles its power.
+    /// `Quad::light` and `Sphere::light` take the area from the shape.
+    pub fn diffuse_light_photometric(color: Color, unit: LightUnit, area: f64) -> Self {
+        // A colour without luminance has no hue to rescale
+        let luminance = color.luminance();
+        let radiance = if luminance > 0.0 {
+            unit.radiance(area) / luminance
+        } else {
+            0.0
+        };
+
+        Self::diffuse_light_from_color(color * radiance).with_two_sided(false)
+    }
+
+    /// Light with the colour of a blackbody at `temperature` Kelvin
+    pub fn diffuse_light_blackbody(temperature: f64, unit: LightUnit, area: f64) -> Self {
+        Self::diffuse_light_photometric(spectrum::blackbody_color(temperature), unit, area)
+    }
+
+    pub fn lambertian(texture: Arc<Texture>) -> Self {
+        Self::Lambertian { texture }
+    }
+
+    pub fn lambertian_from_color(albedo: Color) -> Self {
+        Self::Lambertian {
+            texture: Arc::new(Texture::color(albedo)),
+        }
+    }
+
+    /// Conductor that reflects `albedo` at normal incidence, using it as the edge tint too
+    pub fn metal(albedo: Color, roughness: f64) -> Self {
+        let (eta, k) = Self::conductor_from_albedo(&albedo);
+
+        Self::conductor(eta, k, roughness)
+    }
+
+    fn conductor_from_albedo(albedo: &Color) -> (Color, Color) {
+        let (eta_r, k_r) = fresnel::conductor_from_reflectance(albedo.r, albedo.r);
+        let (eta_g, k_g) = fresnel::conductor_from_reflectance(albedo.g, albedo.g);
+        let (eta_b, k_b) = fresnel::conductor_from_reflectance(albedo.b, albedo.b);
+
+        (Color::new(eta_r, eta_g, eta_b), Color::new(k_r, k_g, k_b))
+    }
+
+    pub fn conductor(eta: Color, k: Color, roughness: f64) -> Self {
+        Self::Metal {
+            eta,
+            k,
+            roughness: roughness.clamp(0.0, 1.0),
+            thin_film: None,
+        }
+    }
+
+    /// Coats a Dielectric or Metal with a thin film, other materials are returned unchanged
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        match &mut self {
+            Material::Dielectric { thin_film, .. } | Material::Metal { thin_film, .. } => {
+                *thin_film = Some(film);
+            }
+            _ => {}
+        }
+
+        self
+    }
+
+    /// Brushed conductor, rougher across the tangent than along it when `roughness_v` is larger
+    pub fn anisotropic_metal(
+        albedo: Color,
+        roughness_u: f64,
+        roughness_v: f64,
+        rotation: f64,
+    ) -> Self {
+        let (eta, k) = Self::conductor_from_albedo(&albedo);
+
+        Self::anisotropic_conductor(
+            eta,
+            k,
+            Arc::new(Texture::scalar(roughness_u)),
+            Arc::new(Texture::scalar(roughness_v)),
+            Arc::new(Texture::scalar(rotation)),
+        )
+    }
+
+    pub fn anisotropic_conductor(
+        eta: Color,
+        k: Color,
+        roughness_u: Arc<Texture>,
+        roughness_v: Arc<Texture>,
+        rotation: Arc<Texture>,
+    ) -> Self {
+        Self::AnisotropicMetal {
+            eta,
+            k,
+            roughness_u,
+            roughness_v,
+            rotation,
+        }
+    }
+
+    pub fn gold(roughness: f64) -> Self {
+        Self::conductor(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::conductor(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::conductor(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::conductor(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn isotropic(texture: Arc<Texture>) -> Self {
+        Self::Isotropic { texture }
+    }
+
+    pub fn isotropic_from_color(albedo: Color) -> Self {
+        Self::Isotropic {
+            texture: Arc::new(Texture::color(albedo)),
+        }
+    }
+
+    pub fn principled(principled: Principled) -> Self {
+        Self::Principled(principled)
+    }
+
+    /// Boundary of a random walk subsurface medium
+    pub fn subsurface(extinction: Color, refraction_index: f64) -> Self {
+        Self::Subsurface {
+            extinction,
+            refraction_index,
+        }
+    }
+
+    /// Scattering event inside a random walk subsurface medium
+    pub fn subsurface_phase(albedo: Color) -> Self {
+        Self::SubsurfacePhase {
+            albedo: Arc::new(Texture::color(albedo)),
+        }
+    }
+
+    pub fn mix(first: Arc<Material>, second: Arc<Material>, factor: f64) -> Self {
+        Self::mix_textured(first, second, Arc::new(Texture::scalar(factor)))
+    }
+
+    pub fn mix_textured(first: Arc<Material>, second: Arc<Material>, mask: Arc<Texture>) -> Self {
+        Self::Mix {
+            first,
+            second,
+            mask,
+        }
+    }
+
+    pub fn coated(base: Arc<Material>, refraction_index: f64, roughness: f64) -> Self {
+        Self::coated_textured(base, refraction_index, Arc::new(Texture::scalar(roughness)))
+    }
+
+    pub fn coated_textured(
+        base: Arc<Material>,
+        refraction_index: f64,
+        roughness: Arc<Texture>,
+    ) -> Self {
+        Self::Coated {
+            base,
+            refraction_index,
+            roughness,
+        }
+    }
+
+    pub fn cloth(albedo: Color, sheen_color: Color, roughness: f64) -> Self {
+        Self::cloth_textured(
+            Arc::new(Texture::color(albedo)),
+            Sheen::charlie(sheen_color, roughness),
+        )
+    }
+
+    pub fn velvet(albedo: Color, sheen_color: Color, roughness: f64) -> Self {
+        Self::cloth_textured(
+            Arc::new(Texture::color(albedo)),
+            Sheen::velvet(sheen_color, roughness),
+        )
+    }
+
+    pub fn cloth_textured(albedo: Arc<Texture>, sheen: Sheen) -> Self {
+        Self::Cloth { albedo, sheen }
+    }
+
+    /// Makes the material glow with `emission` scaled by `strength`, from its front face only by default
+    pub fn with_emission(self, emission: Arc<Texture>, strength: f64) -> Self {
+        Self::Emissive {
+            base: Arc::new(self),
+            emission,
+            strength,
+            two_sided: false,
+        }
+    }
+
+    /// Chooses whether a light or emissive material also emits from its back face
+    ///
+    /// Reaches emitters nested in wrappers and mixes, copying bases shared with other materials.
+    pub fn with_two_sided(mut self, two_sided: bool) -> Self {
+        self.set_two_sided(two_sided);
+        self
+    }
+
+    fn set_two_sided(&mut self, two_sided: bool) {
+        match self {
+            Self::DiffuseLight {
+                two_sided: flag, ..
+            } => *flag = two_sided,
+            Self::Principled(principled) => *principled = principled.clone().two_sided(two_sided),
+            Self::Emissive {
+                base,
+                two_sided: flag,
+                ..
+            } => {
+                *flag = two_sided;
+                Arc::make_mut(base).set_two_sided(two_sided);
+            }
+            Self::Coated { base, .. }
+            | Self::Cutout { base, .. }
+            | Self::NormalMapped { base, .. } => Arc::make_mut(base).set_two_sided(two_sided),
+            Self::Mix { first, second, .. } => {
+                Arc::make_mut(first).set_two_sided(two_sided);
+                Arc::make_mut(second).set_two_sided(two_sided);
+            }
+            _ => (),
+        }
+    }
+
+    /// Perturbs the shading normal with a tangent space normal map
+    pub fn with_normal_map(self, texture: Arc<Texture>) -> Self {
+        Self::NormalMapped {
+            base: Arc::new(self),
+            map: NormalMap::Tangent { texture },
+        }
+    }
+
+    /// Perturbs the shading normal with a height texture, `scale` being the height of a luminance of one
+    pub fn with_bump_map(self, height: Arc<Texture>, scale: f64) -> Self {
+        Self::NormalMapped {
+            base: Arc::new(self),
+            map: NormalMap::Bump { height, scale },
+        }
+    }
+
+    /// Cuts out the material where `opacity` is below a random threshold, see `Texture::opacity`
+    pub fn with_opacity(self, opacity: Arc<Texture>) -> Self {
+        Self::Cutout {
+            base: Arc::new(self),
+            opacity,
+        }
+    }
+}
+
+impl Material {
+    pub fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        match self {
+            Material::Dielectric {
+                albedo,
+                refraction_index,
+                absorption,
+                dispersion,
+                thin_film,
+            } => {
+                let refraction_index = match (dispersion, ray.wavelength) {
+                    (Some(dispersion), Some(lambda)) => dispersion.refraction_index(lambda),
+                    _ => *refraction_index,
+                };
+
+                Self::dielectric_scatter(
+                    albedo,
+                    refraction_index,
+                    absorption,
+                    thin_film.as_ref(),
+                    ray,
+                    hit_record,
+                )
+            }
+            Material::RoughDielectric {
+                albedo,
+                refraction_index,
+                roughness,
+            } => Self::rough_dielectric_scatter(
+                albedo,
+                *refraction_index,
+                roughness,
+                ray,
+                hit_record,
+            ),
+            Material::DiffuseLight { .. } => None,
+            Material::Lambertian { texture } => Self::lambertian_scatter(texture, ray, hit_record),
+            Material::Metal {
+                eta,
+                k,
+                roughness,
+                thin_film,
+            } => Self::metal_scatter(eta, k, *roughness, thin_film.as_ref(), ray, hit_record),
+            Material::AnisotropicMetal {
+                eta,
+                k,
+                roughness_u,
+                roughness_v,
+                rotation,
+            } => Self::anisotropic_metal_scatter(
+                eta,
+                k,
+                roughness_u,
+                roughness_v,
+                rotation,
+                ray,
+                hit_record,
+            ),
+            Material::Isotropic { texture } => Self::isotropic_scatter(texture, ray, hit_record),
+            Material::Principled(principled) => principled.scatter(ray, hit_record),
+            Material::Mix {
+                first,
+                second,
+                mask,
+            } => {
+                let mask = mask.value_at(hit_record).luminance();
+
+                if gen_f64() < mask {
+                    second.scatter(ray, hit_record)
+                } else {
+                    first.scatter(ray, hit_record)
+                }
+            }
+            Material::Subsurface {
+                refraction_index, ..
+            } => Self::dielectric_scatter(
+                &Color::white(),
+                *refraction_index,
+                &Color::black(),
+                None,
+                ray,
+                hit_record,
+            ),
+            Material::SubsurfacePhase { albedo } => {
+                Self::subsurface_phase_scatter(albedo, ray, hit_record)
+            }
+            Material::Coated {
+                base,
+                refraction_index,
+                roughness,
+            } => Self::coated_scatter(base, *refraction_index, roughness, ray, hit_record),
+            Material::Cloth { albedo, sheen } => {
+                Self::cloth_scatter(albedo, sheen, ray, hit_record)
+            }
+            Material::Cutout { base, .. } | Material::Emissive { base, .. } => {
+                base.scatter(ray, hit_record)
+            }
+            Material::NormalMapped { base, map } => {
+                let shading_hit = Self::normal_mapped_hit(map, ray, hit_record);
+                let (attenuation, scattered) = base.scatter(ray, &shading_hit)?;
+
+                // Directions on different sides of the real and shading surfaces would leak light through it
+                let geometric_cos = scattered.direction.dot(&hit_record.normal);
+                let shading_cos = scattered.direction.dot(&shading_hit.normal);
+                if geometric_cos * shading_cos <= 0.0 {
+                    return None;
+                }
+
+                Some((attenuation, scattered))
+            }
+        }
+    }
+
+    /// The hit as the base of a normal mapped material sees it, with the shading normal in place of the real one
+    fn normal_mapped_hit(map: &NormalMap, ray: &Ray, hit_record: &HitRecord) -> HitRecord {
+        let mut shading_hit = hit_record.clone();
+        shading_hit.normal = map.shading_normal(hit_record, &-ray.direction.normalize());
+
+        shading_hit
+    }
+
+    /// Calculates Schlick's approximation for reflectance
+    fn dielectric_reflectance(cos: f64, refraction_index: f64) -> f64 {
+        // Use Schlick's approximation for reflectance
+        let mut r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
+        r0 = r0 * r0;
+
+        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+    }
+
+    fn dielectric_scatter(
+        albedo: &Color,
+        refraction_index: f64,
+        absorption: &Color,
+        thin_film: Option<&ThinFilm>,
+        ray: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let (eta_i, eta_t) = if hit_record.front_face {
+            (1.0, refraction_index)
+        } else {
+            (refraction_index, 1.0)
+        };
+        let refraction_index = eta_i / eta_t;
+
+        let unit_direction = ray.direction.normalize();
+        let cos = (-unit_direction).dot(&hit_record.normal).min(1.0);
+        let sin = (1.0 - cos * cos).sqrt();
+
+        let cannot_refract = refraction_index * sin > 1.0;
+
+        let reflected = unit_direction.reflected(&hit_record.normal);
+        let refracted = || {
+            unit_direction
+                .normalize()
+                .refracted(&hit_record.normal, refraction_index)
+        };
+
+        let (refracts, weight) = if cannot_refract {
+            (false, Color::white())
+        } else if let Some(thin_film) = thin_film {
+            // Coloured reflectance, so choose by its average and weight each channel
+            let reflectance = thin_film.reflectance(
+                cos,
+                eta_i,
+                |_| Complex::real(eta_t),
+                hit_record.uv,
+                &hit_record.pos,
+            );
+            let probability = (reflectance.r + reflectance.g + reflectance.b) / 3.0;
+
+            if gen_f64() < probability {
+                (false, reflectance * (1.0 / probability))
+            } else {
+                let transmittance = Color::new(
+                    1.0 - reflectance.r,
+                    1.0 - reflectance.g,
+                    1.0 - reflectance.b,
+                );
+                (true, transmittance * (1.0 / (1.0 - probability)))
+            }
+        } else if Self::dielectric_reflectance(cos, refraction_index) > tls_rng().generate() {
+            (false, Color::white())
+        } else {
+            (true, Color::white())
+        };
+
+        let (direction, differentials) = if refracts {
+            (
+                refracted(),
+                Self::refracted_differentials(ray, hit_record, refraction_index),
+            )
+        } else {
+            (reflected, Self::reflected_differentials(ray, hit_record))
+        };
+
+        // Hitting the inside means the ray has travelled through the material
+        let attenuation = if hit_record.front_face {
+            *albedo * weight
+        } else {
+            let distance = hit_record.t * ray.direction.length();
+
+            *albedo
+                * weight
+                * Color::new(
+                    (-absorption.r * distance).exp(),
+                    (-absorption.g * distance).exp(),
+                    (-absorption.b * distance).exp(),
+                )
+        };
+
+        Some((
+            attenuation,
+            Ray::new(hit_record.pos, direction, ray.time).with_differentials(differentials),
+        ))
+    }
+
+    /// Differentials of a mirror reflection, treating the surface as locally flat
+    fn reflected_differentials(ray: &Ray, hit_record: &HitRecord) -> Option<RayDifferentials> {
+        let differentials = ray.differentials?;
+
+        Some(RayDifferentials {
+            rx_origin: hit_record.pos + hit_record.dpdx,
+            rx_direction: differentials.rx_direction.reflected(&hit_record.normal),
+            ry_origin: hit_record.pos + hit_record.dpdy,
+            ry_direction: differentials.ry_direction.reflected(&hit_record.normal),
+        })
+    }
+
+    /// Differentials of a smooth refraction, `refraction_index` being the incident over transmitted ior
+    fn refracted_differentials(
+        ray: &Ray,
+        hit_record: &HitRecord,
+        refraction_index: f64,
+    ) -> Option<RayDifferentials> {
+        let differentials = ray.differentials?;
+        let refract = |direction: &Vec3| {
+            direction
+                .normalize()
+                .refracted(&hit_record.normal, refraction_index)
+        };
+
+        Some(RayDifferentials {
+            rx_origin: hit_record.pos + hit_record.dpdx,
+            rx_direction: refract(&differentials.rx_direction),
+            ry_origin: hit_record.pos + hit_record.dpdy,
+            ry_direction: refract(&differentials.ry_direction),
+        })
+    }
+
+    /// Microfacet reflection and transmission (Walter et al. 2007)
+    fn rough_dielectric_scatter(
+        albedo: &Color,
+        refraction_index: f64,
+        roughness: &Arc<Texture>,
+        ray: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        // Ratio of transmitted over incident ior
+        let eta = if hit_record.front_face {
+            refraction_index
+        } else {
+            1.0 / refraction_index
+        };
+
+        let basis = OrthonormalBasis::from_w(&hit_record.normal);
+        let wo = basis.to_local(&-ray.direction.normalize());
+
+        let roughness = roughness.value_at(hit_record).luminance().clamp(0.0, 1.0);
+        let distribution = TrowbridgeReitz::from_roughness(roughness);
+
+        let (wi, weight) = Self::sample_microfacet_dielectric(&distribution, &wo, eta)?;
+
+        Some((
+            *albedo * weight,
+            Ray::new(hit_record.pos, basis.to_world(&wi), ray.time),
+        ))
+    }
+
+    /// Samples a visible microfacet normal in local space, or the macrosurface normal when smooth
+    fn sample_microfacet_normal(distribution: &TrowbridgeReitz, wo: &Vec3) -> Vec3 {
+        if distribution.is_smooth() {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            distribution.sample_visible_normal(wo, gen_f64(), gen_f64())
+        }
+    }
+
+    /// Reflects about a sampled microfacet normal, returning `wi` and the weight `G2 / G1` left after visible normal sampling
+    fn reflect_microfacet(
+        distribution: &TrowbridgeReitz,
+        wo: &Vec3,
+        wm: &Vec3,
+    ) -> Option<(Vec3, f64)> {
+        let wi = (-*wo).reflected(wm);
+
+        if distribution.is_smooth() {
+            return Some((wi, 1.0));
+        }
+        if wi.z <= 0.0 {
+            return None;
+        }
+
+        Some((wi, distribution.g(wo, &wi) / distribution.g1(wo)))
+    }
+
+    /// Samples a microfacet normal and reflects about it, giving the local space `(wm, wi, weight)`
+    fn sample_microfacet_reflection(
+        distribution: &TrowbridgeReitz,
+        wo: &Vec3,
+    ) -> Option<(Vec3, Vec3, f64)> {
+        let wm = Self::sample_microfacet_normal(distribution, wo);
+        let (wi, weight) = Self::reflect_microfacet(distribution, wo, &wm)?;
+
+        Some((wm, wi, weight))
+    }
+
+    /// Microfacet reflection and transmission (Walter et al. 2007), `eta` is the transmitted over incident ior
+    ///
+    /// Returns the local space `wi` and its weight
+    fn sample_microfacet_dielectric(
+        distribution: &TrowbridgeReitz,
+        wo: &Vec3,
+        eta: f64,
+    ) -> Option<(Vec3, f64)> {
+        let wm = Self::sample_microfacet_normal(distribution, wo);
+
+        let cos_o = wo.dot(&wm);
+        let reflectance = fresnel::dielectric(cos_o, eta);
+
+        // Choose between reflection and transmission by the Fresnel term, which then cancels out
+        let wi = if gen_f64() < reflectance {
+            let wi = (-*wo).reflected(&wm);
+            if wi.z <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let cos_t = (1.0 - (1.0 - cos_o * cos_o) / (eta * eta)).max(0.0).sqrt();
+            let wi = -*wo / eta + (cos_o / eta - cos_t) * wm;
+            if wi.z >= 0.0 {
+                return None;
+            }
+            wi
+        };
+
+        if distribution.is_smooth() {
+            Some((wi, 1.0))
+        } else {
+            Some((wi, distribution.g(wo, &wi) / distribution.g1(wo)))
+        }
+    }
+
+    /// Reflects off the coat with its Fresnel probability, otherwise scatters off the base
+    fn coated_scatter(
+        base: &Material,
+        refraction_index: f64,
+        roughness: &Arc<Texture>,
+        ray: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        // Light inside the object never reaches the coat
+        if !hit_record.front_face {
+            return base.scatter(ray, hit_record);
+        }
+
+        let basis = OrthonormalBasis::from_w(&hit_record.normal);
+        let wo = basis.to_local(&-ray.direction.normalize());
+
+        let roughness = roughness.value_at(hit_record).luminance().clamp(0.0, 1.0);
+        let distribution = TrowbridgeReitz::from_roughness(roughness);
+
+        match Self::sample_coat(&distribution, &wo, refraction_index, 1.0) {
+            Some(wm) => {
+                let (wi, weight) = Self::reflect_microfacet(&distribution, &wo, &wm)?;
+
+                Some((
+                    Color::white() * weight,
+                    Ray::new(hit_record.pos, basis.to_world(&wi), ray.time),
+                ))
+            }
+            None => base.scatter(ray, hit_record),
+        }
+    }
+
+    /// Samples a microfacet normal on a dielectric coat, returning it if the coat reflects
+    ///
+    /// Light passing the coat is approximated by the chance of not reflecting, so needs no weight.
+    fn sample_coat(
+        distribution: &TrowbridgeReitz,
+        wo: &Vec3,
+        refraction_index: f64,
+        strength: f64,
+    ) -> Option<Vec3> {
+        let wm = Self::sample_microfacet_normal(distribution, wo);
+
+        (gen_f64() < strength * fresnel::dielectric(wo.dot(&wm), refraction_index)).then_some(wm)
+    }
+
+    fn cloth_scatter(
+        albedo: &Arc<Texture>,
+        sheen: &Sheen,
+        ray: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let basis = OrthonormalBasis::from_w(&hit_record.normal);
+        let wo = basis.to_local(&-ray.direction.normalize());
+
+        // Cosine weighted hemisphere sample
+        let mut wi = Vec3::new(0.0, 0.0, 1.0) + Vec3::random_unit();
+        if wi.near_zero() {
+            wi = Vec3::new(0.0, 0.0, 1.0);
+        }
+        let wi = wi.normalize();
+
+        // f * cos / pdf, the cosine pdf leaves a factor of pi
+        let diffuse = albedo.value_at(hit_record) * (1.0 / PI);
+        let attenuation = sheen.layer(diffuse, &wo, &wi, hit_record.uv, &hit_record.pos) * PI;
+
+        Some((
+            attenuation,
+            Ray::new(hit_record.pos, basis.to_world(&wi), ray.time),
+        ))
+    }
+
+    fn lambertian_scatter(
+        texture: &Arc<Texture>,
+        ray: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let mut scatter_direction = hit_record.normal + Vec3::random_unit();
+
+        if scatter_direction.near_zero() {
+            scatter_direction = hit_record.normal;
+        }
+
+        Some((
+            texture.value_at(hit_record),
+            Ray::new(hit_record.pos, scatter_direction, ray.time),
+        ))
+    }
+
+    fn metal_scatter(
+        eta: &Color,
+        k: &Color,
+        roughness: f64,
+        thin_film: Option<&ThinFilm>,
+        ray: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let basis = OrthonormalBasis::from_w(&hit_record.normal);
+        let wo = basis.to_local(&-ray.direction.normalize());
+
+        let distribution = TrowbridgeReitz::from_roughness(roughness);
+
+        // f * cos / pdf for visible normal sampling reduces to F * G2 / G1
+        let (wm, wi, weight) = Self::sample_microfacet_reflection(&distribution, &wo)?;
+
+        let reflectance = match thin_film {
+            None => fresnel::conductor_color(wo.dot(&wm), eta, k),
+            Some(thin_film) => {
+                // Spectral ior approximated by the colour channel covering each wavelength
+                let substrate = |lambda: f64| {
+                    if lambda < 490.0 {
+                        Complex::new(eta.b, k.b)
+                    } else if lambda < 590.0 {
+                        Complex::new(eta.g, k.g)
+                    } else {
+                        Complex::new(eta.r, k.r)
+                    }
+                };
+
+                thin_film.reflectance(wo.dot(&wm), 1.0, substrate, hit_record.uv, &hit_record.pos)
+            }
+        };
+
+        // Only mirror reflections keep the footprint narrow enough to track
+        let differentials = if distribution.is_smooth() {
+            Self::reflected_differentials(ray, hit_record)
+        } else {
+            None
+        };
+
+        Some((
+            reflectance * weight,
+            Ray::new(hit_record.pos, basis.to_world(&wi), ray.time)
+                .with_differentials(differentials),
+        ))
+    }
+
+    fn subsurface_phase_scatter(
+        albedo: &Arc<Texture>,
+        ray: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let (albedo, scattered) = Self::isotropic_scatter(albedo, ray, hit_record)?;
+
+        // Distances are sampled exactly for the path's channel or wavelength, leaving only its albedo
+        let attenuation = match (ray.wavelength, ray.channel) {
+            (Some(lambda), _) => {
+                let albedo = spectrum::rgb_to_spectrum(&albedo, lambda).clamp(0.0, 1.0);
+                Color::new(albedo, albedo, albedo)
+            }
+            (None, Some(channel)) => {
+                let mask = |n: usize| if n == channel { albedo.get(n) } else { 0.0 };
+                Color::new(mask(0), mask(1), mask(2))
+            }
+            (None, None) => albedo,
+        };
+
+        Some((attenuation, scattered))
+    }
+
+    /// GGX conductor with separate roughness along the surface's tangent and bitangent
+    fn anisotropic_metal_scatter(
+        eta: &Color,
+        k: &Color,
+        roughness_u: &Arc<Texture>,
+        roughness_v: &Arc<Texture>,
+        rotation: &Arc<Texture>,
+        ray: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let value = |texture: &Arc<Texture>| texture.value_at(hit_record).luminance();
+
+        // Tangent frame rotated around the normal
+        let basis = OrthonormalBasis::from_w_and_tangent(&hit_record.normal, &hit_record.dpdu);
+        let (sin, cos) = (2.0 * PI * value(rotation)).sin_cos();
+        let basis = OrthonormalBasis::from_w_and_tangent(
+            &hit_record.normal,
+            &(cos * basis.u + sin * basis.v),
+        );
+
+        let wo = basis.to_local(&-ray.direction.normalize());
+
+        let roughness_u = value(roughness_u).clamp(0.0, 1.0);
+        let roughness_v = value(roughness_v).clamp(0.0, 1.0);
+        let distribution =
+            TrowbridgeReitz::new(roughness_u * roughness_u, roughness_v * roughness_v);
+
+        let (wm, wi, weight) = Self::sample_microfacet_reflection(&distribution, &wo)?;
+        let reflectance = fresnel::conductor_color(wo.dot(&wm), eta, k);
+
+        Some((
+            reflectance * weight,
+            Ray::new(hit_record.pos, basis.to_world(&wi), ray.time),
+        ))
+    }
+
+    fn isotropic_scatter(
+        texture: &Arc<Texture>,
+        ray: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let scattered = Ray::new(hit_record.pos, Vec3::random_unit(), ray.time);
+        let attenuation = texture.value_at(hit_record);
+
+        Some((attenuation, scattered))
+    }
+
+    /// Whether scattering depends on the wavelength, so paths hitting it need one sampled
+    pub fn is_dispersive(&self) -> bool {
+        match self {
+            Material::Dielectric { dispersion, .. } => dispersion.is_some(),
+            Material::Mix { first, second, .. } => first.is_dispersive() || second.is_dispersive(),
+            Material::Coated { base, .. }
+            | Material::Cutout { base, .. }
+            | Material::Emissive { base, .. }
+            | Material::NormalMapped { base, .. } => base.is_dispersive(),
+            _ => false,
+        }
+    }
+
+    /// Whether `scatter` only samples cosine weighted directions, so any other direction can be weighted with
+    /// `diffuse_eval` and `diffuse_pdf`
+    ///
+    /// Layered materials such as `Principled` and `Coated` pick a specular lobe at random, so they are not.
+    pub fn is_diffuse(&self) -> bool {
+        match self {
+            Material::Lambertian { .. } | Material::Cloth { .. } => true,
+            Material::Cutout { base, .. }
+            | Material::Emissive { base, .. }
+            | Material::NormalMapped { base, .. } => base.is_diffuse(),
+            Material::Mix { first, second, .. } => first.is_diffuse() && second.is_diffuse(),
+            _ => false,
+        }
+    }
+
+    /// f * cos of scattering `ray` towards `direction`, for materials that are `is_diffuse`
+    pub fn diffuse_eval(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        let cos = hit_record.normal.dot(&direction.normalize()).max(0.0);
+
+        match self {
+            Material::Lambertian { texture } => texture.value_at(hit_record) * (cos / PI),
+            Material::Cloth { albedo, sheen } => {
+                let basis = OrthonormalBasis::from_w(&hit_record.normal);
+                let wo = basis.to_local(&-ray.direction.normalize());
+                let wi = basis.to_local(&direction.normalize());
+
+                let diffuse = albedo.value_at(hit_record) * (1.0 / PI);
+
+                sheen.layer(diffuse, &wo, &wi, hit_record.uv, &hit_record.pos) * cos
+            }
+            Material::Cutout { base, .. } | Material::Emissive { base, .. } => {
+                base.diffuse_eval(ray, hit_record, direction)
+            }
+            Material::NormalMapped { base, map } => {
+                // Same as the directions `scatter` rejects
+                if direction.dot(&hit_record.normal) <= 0.0 {
+                    return Color::black();
+                }
+
+                base.diffuse_eval(
+                    ray,
+                    &Self::normal_mapped_hit(map, ray, hit_record),
+                    direction,
+                )
+            }
+            Material::Mix {
+                first,
+                second,
+                mask,
+            } => {
+                let mask = mask.value_at(hit_record).luminance().clamp(0.0, 1.0);
+
+                first.diffuse_eval(ray, hit_record, direction) * (1.0 - mask)
+                    + second.diffuse_eval(ray, hit_record, direction) * mask
+            }
+            _ => Color::black(),
+        }
+    }
+
+    /// Density of `scatter` choosing `direction`, for materials that are `is_diffuse`
+    pub fn diffuse_pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        match self {
+            Material::Lambertian { .. } | Material::Cloth { .. } => {
+                hit_record.normal.dot(&direction.normalize()).max(0.0) / PI
+            }
+            Material::Cutout { base, .. } | Material::Emissive { base, .. } => {
+                base.diffuse_pdf(ray, hit_record, direction)
+            }
+            Material::NormalMapped { base, map } => base.diffuse_pdf(
+                ray,
+                &Self::normal_mapped_hit(map, ray, hit_record),
+                direction,
+            ),
+            Material::Mix {
+                first,
+                second,
+                mask,
+            } => {
+                let mask = mask.value_at(hit_record).luminance().clamp(0.0, 1.0);
+
+                first.diffuse_pdf(ray, hit_record, direction) * (1.0 - mask)
+                    + second.diffuse_pdf(ray, hit_record, direction) * mask
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Stochastic alpha test, hits failing it should be ignored by the hittable
+    pub fn is_opaque_at(&self, uv: (f64, f64), pos: &Vec3) -> bool {
+        let opacity = self.opacity_at(uv, pos);
+
+        opacity >= 1.0 || gen_f64() < opacity
+    }
+
+    /// Chance of a hit counting, wrappers pass on their base's and mixes blend by the mask
+    fn opacity_at(&self, uv: (f64, f64), pos: &Vec3) -> f64 {
+        match self {
+            Material::Cutout { opacity, .. } => opacity.opacity(uv, pos).clamp(0.0, 1.0),
+            Material::Emissive { base, .. }
+            | Material::NormalMapped { base, .. }
+            | Material::Coated { base, .. } => base.opacity_at(uv, pos),
+            Material::Mix {
+                first,
+                second,
+                mask,
+            } => {
+                let mask = mask.value(uv, pos).luminance().clamp(0.0, 1.0);
+
+                first.opacity_at(uv, pos) * (1.0 - mask) + second.opacity_at(uv, pos) * mask
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Whether the material bounds a medium sampled differently per colour channel or wavelength
+    pub fn is_chromatic(&self) -> bool {
+        match self {
+            Material::Subsurface { extinction, .. } => {
+                extinction.r != extinction.g || extinction.g != extinction.b
+            }
+            _ => false,
+        }
+    }
+
+    /// Light leaving the hit surface, back faces of one-sided emitters are dark
+    pub fn emitted(&self, hit_record: &HitRecord) -> Color {
+        match self {
+            Material::DiffuseLight { texture, two_sided } => {
+                if *two_sided || hit_record.front_face {
+                    texture.value_at(hit_record)
+                } else {
+                    Color::black()
+                }
+            }
+            Material::Principled(principled) => principled.emitted(hit_record),
+            Material::Mix {
+                first,
+                second,
+                mask,
+            } => {
+                let mask = mask.value_at(hit_record).luminance().clamp(0.0, 1.0);
+
+                first.emitted(hit_record) * (1.0 - mask) + second.emitted(hit_record) * mask
+            }
+            Material::Coated { base, .. }
+            | Material::Cutout { base, .. }
+            | Material::NormalMapped { base, .. } => base.emitted(hit_record),
+            Material::Emissive {
+                base,
+                emission,
+                strength,
+                two_sided,
+            } => {
+                let emission = if *two_sided || hit_record.front_face {
+                    emission.value_at(hit_record) * *strength
+                } else {
+                    Color::black()
+                };
+
+                base.emitted(hit_record) + emission
+            }
+            _ => Color::black(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cut_out(opacity: f64) -> Arc<Material> {
+        Arc::new(
+            Material::lambertian_from_color(Color::white())
+                .with_opacity(Arc::new(Texture::scalar(opacity))),
+        )
+    }
+
+    #[test]
+    fn coats_keep_their_base_cut_out() {
+        let coated = Material::coated(cut_out(0.0), 1.5, 0.2);
+
+        assert_eq!(coated.opacity_at((0.5, 0.5), &Vec3::zero()), 0.0);
+        assert!(!coated.is_opaque_at((0.5, 0.5), &Vec3::zero()));
+    }
+
+    #[test]
+    fn mixes_blend_their_opacity_by_the_mask() {
+        let solid = Arc::new(Material::lambertian_from_color(Color::white()));
+        let mix = Material::mix(cut_out(0.2), solid, 0.25);
+
+        let opacity = mix.opacity_at((0.5, 0.5), &Vec3::zero());
+
+        assert!((opacity - 0.4).abs() < 1e-9, "{}", opacity);
+    }
+
+    fn back_face_hit(material: &Arc<Material>) -> HitRecord {
+        HitRecord::new(Vec3::zero(), 1.0, Arc::clone(material), (0.5, 0.5))
+    }
+
+    #[test]
+    fn one_sided_reaches_nested_emitters() {
+        let glow = Arc::new(Texture::scalar(1.0));
+        let light = Arc::new(Material::diffuse_light(Arc::clone(&glow)));
+        let principled = Arc::new(Material::principled(
+            Principled::new().emission(Arc::clone(&glow)),
+        ));
+
+        let mix = Arc::new(
+            Material::mix(Arc::clone(&light), Arc::clone(&principled), 0.5)
+                .with_normal_map(Arc::clone(&glow))
+                .with_two_sided(false),
+        );
+        assert_eq!(mix.emitted(&back_face_hit(&mix)).luminance(), 0.0);
+
+        // The shared children are copied rather than changed
+        assert!(light.emitted(&back_face_hit(&light)).luminance() > 0.0);
+        assert!(principled.emitted(&back_face_hit(&principled)).luminance() > 0.0);
+    }
+
+    #[test]
+    fn subsurface_phase_keeps_the_path_channel() {
+        let phase = Arc::new(Material::subsurface_phase(Color::new(0.2, 0.5, 0.8)));
+        let hit_record = back_face_hit(&phase);
+
+        let ray = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 0.0).with_channel(Some(1));
+        let (attenuation, _) = phase.scatter(&ray, &hit_record).unwrap();
+
+        assert_eq!(
+            (attenuation.r, attenuation.g, attenuation.b),
+            (0.0, 0.5, 0.0)
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use crate::{hittable::HitRecord, onb::OrthonormalBasis, texture::Texture, vec3::Vec3};
+
+/// Step in uv used to difference bump maps
+const BUMP_DELTA: f64 = 0.0005;
+
+/// Smallest cosine kept between the shading normal and the incoming direction
+const MIN_SHADING_COS: f64 = 0.01;
+
+/// Perturbation of the shading normal from a texture
+#[derive(Clone)]
+pub enum NormalMap {
+    /// Tangent space normal map, with red along dpdu, green along dpdv and blue along the normal
+    ///
+    /// Image normal maps should be loaded with `ColorSpace::Linear`.
+    Tangent { texture: Arc<Texture> },
+    /// Height field along the normal, taken from the texture's luminance
+    Bump { height: Arc<Texture>, scale: f64 },
+}
+
+impl NormalMap {
+    /// Shading normal at the hit, on the same side as its geometric normal
+    pub fn perturb(&self, hit_record: &HitRecord) -> Vec3 {
+        match self {
+            NormalMap::Tangent { texture } => Self::tangent_normal(texture, hit_record),
+            NormalMap::Bump { height, scale } => Self::bump_normal(height, *scale, hit_record),
+        }
+    }
+
+    /// `perturb` bent towards `wo` where the map tilts it away, as PBRT does, so `wo` stays above it
+    pub fn shading_normal(&self, hit_record: &HitRecord, wo: &Vec3) -> Vec3 {
+        let normal = self.perturb(hit_record);
+        let cos = wo.dot(&normal);
+
+        if cos >= MIN_SHADING_COS {
+            normal
+        } else {
+            (normal + (MIN_SHADING_COS - cos) * *wo).normalize()
+        }
+    }
+
+    fn tangent_normal(texture: &Texture, hit_record: &HitRecord) -> Vec3 {
+        let normal = hit_record.normal;
+        let basis = OrthonormalBasis::from_w_and_tangent(&normal, &hit_record.dpdu);
+
+        // Back faces and mirrored parametrisations flip the bitangent relative to dpdv
+        let bitangent = if basis.v.dot(&hit_record.dpdv) < 0.0 {
+            -basis.v
+        } else {
+            basis.v
+        };
+
+        let color = texture.value_at(hit_record);
+        let local = Vec3::new(
+            2.0 * color.r - 1.0,
+            2.0 * color.g - 1.0,
+            2.0 * color.b - 1.0,
+        );
+
+        let perturbed = local.x * basis.u + local.y * bitangent + local.z * normal;
+
+        if perturbed.near_zero() {
+            normal
+        } else {
+            perturbed.normalize()
+        }
+    }
+
+    /// Differences the height along dpdu and dpdv (Blinn 1978)
+    fn bump_normal(height: &Texture, scale: f64, hit_record: &HitRecord) -> Vec3 {
+        let (normal, dpdu, dpdv) = (hit_record.normal, hit_record.dpdu, hit_record.dpdv);
+        let (u, v) = hit_record.uv;
+
+        if dpdu.near_zero() || dpdv.near_zero() {
+            return normal;
+        }
+
+        let height = |uv: (f64, f64), pos: &Vec3| scale * height.value(uv, pos).luminance();
+
+        let displacement = height(hit_record.uv, &hit_record.pos);
+        let displacement_u = height((u + BUMP_DELTA, v), &(hit_record.pos + BUMP_DELTA * dpdu));
+        let displacement_v = height((u, v + BUMP_DELTA), &(hit_record.pos + BUMP_DELTA * dpdv));
+
+        let dpdu = dpdu + (displacement_u - displacement) / BUMP_DELTA * normal;
+        let dpdv = dpdv + (displacement_v - displacement) / BUMP_DELTA * normal;
+
+        let perturbed = dpdu.cross(&dpdv);
+        if perturbed.near_zero() {
+            return normal;
+        }
+
+        let perturbed = perturbed.normalize();
+
+        if perturbed.dot(&normal) < 0.0 {
+            -perturbed
+        } else {
+            perturbed
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    fresnel, hittable::HitRecord, image::color::Color, microfacet::TrowbridgeReitz,
+    onb::OrthonormalBasis, random::gen_f64, ray::Ray, texture::Texture, vec3::Vec3,
+};
+
+use super::{sheen::Sheen, Material};
+
+/// Ior of the clearcoat layer
+const CLEARCOAT_IOR: f64 = 1.5;
+
+/// Disney-style uber material, every parameter is a texture
+///
+/// Colour parameters use the texture's value, scalar parameters its luminance.
+#[derive(Clone)]
+pub struct Principled {
+    base_color: Arc<Texture>,
+    metallic: Arc<Texture>,
+    roughness: Arc<Texture>,
+    // Scales the Fresnel reflectance given by the ior, 0.5 leaves it unchanged
+    specular: Arc<Texture>,
+    // Fabric sheen over the diffuse lobe
+    sheen: Sheen,
+    clearcoat: Arc<Texture>,
+    clearcoat_roughness: Arc<Texture>,
+    transmission: Arc<Texture>,
+    refraction_index: Arc<Texture>,
+    emission: Arc<Texture>,
+    // Otherwise only the front face emits
+    two_sided: bool,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Principled {
+    pub fn new() -> Self {
+        Self {
+            base_color: Arc::new(Texture::scalar(0.8)),
+            metallic: Arc::new(Texture::scalar(0.0)),
+            roughness: Arc::new(Texture::scalar(0.5)),
+            specular: Arc::new(Texture::scalar(0.5)),
+            sheen: Sheen::charlie(Color::black(), 0.5),
+            clearcoat: Arc::new(Texture::scalar(0.0)),
+            clearcoat_roughness: Arc::new(Texture::scalar(0.03)),
+            transmission: Arc::new(Texture::scalar(0.0)),
+            refraction_index: Arc::new(Texture::scalar(1.5)),
+            emission: Arc::new(Texture::scalar(0.0)),
+            two_sided: true,
+        }
+    }
+
+    pub fn base_color(mut self, base_color: Arc<Texture>) -> Self {
+        self.base_color = base_color;
+        self
+    }
+
+    pub fn metallic(mut self, metallic: Arc<Texture>) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn roughness(mut self, roughness: Arc<Texture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn specular(mut self, specular: Arc<Texture>) -> Self {
+        self.specular = specular;
+        self
+    }
+
+    pub fn sheen(mut self, sheen: Arc<Texture>) -> Self {
+        self.sheen = self.sheen.with_color(sheen);
+        self
+    }
+
+    pub fn sheen_roughness(mut self, sheen_roughness: Arc<Texture>) -> Self {
+        self.sheen = self.sheen.with_roughness(sheen_roughness);
+        self
+    }
+
+    /// Replaces the sheen lobe, e.g. to use a velvet distribution
+    pub fn sheen_lobe(mut self, sheen: Sheen) -> Self {
+        self.sheen = sheen;
+        self
+    }
+
+    pub fn clearcoat(mut self, clearcoat: Arc<Texture>) -> Self {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    pub fn clearcoat_roughness(mut self, clearcoat_roughness: Arc<Texture>) -> Self {
+        self.clearcoat_roughness = clearcoat_roughness;
+        self
+    }
+
+    pub fn transmission(mut self, transmission: Arc<Texture>) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    pub fn refraction_index(mut self, refraction_index: Arc<Texture>) -> Self {
+        self.refraction_index = refraction_index;
+        self
+    }
+
+    pub fn emission(mut self, emission: Arc<Texture>) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    /// Whether the back face emits too, which it does by default
+    pub fn two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+}
+
+impl Principled {
+    /// Picks one lobe at random, so each returned weight is its f * cos / pdf divided by the lobe's probability
+    pub fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let basis = OrthonormalBasis::from_w(&hit_record.normal);
+        let wo = basis.to_local(&-ray.direction.normalize());
+
+        let color = |texture: &Arc<Texture>| texture.value_at(hit_record);
+        let scalar = |texture: &Arc<Texture>| color(texture).luminance().clamp(0.0, 1.0);
+
+        // Clearcoat only covers the outside
+        let clearcoat = if hit_record.front_face {
+            scalar(&self.clearcoat)
+        } else {
+            0.0
+        };
+
+        if clearcoat > 0.0 {
+            let distribution = TrowbridgeReitz::from_roughness(scalar(&self.clearcoat_roughness));
+
+            if let Some(wm) = Material::sample_coat(&distribution, &wo, CLEARCOAT_IOR, clearcoat) {
+                let (wi, weight) = Material::reflect_microfacet(&distribution, &wo, &wm)?;
+
+                return Some((
+                    Color::white() * weight,
+                    Ray::new(hit_record.pos, basis.to_world(&wi), ray.time),
+                ));
+            }
+        }
+
+        let base_color = color(&self.base_color);
+        let metallic = scalar(&self.metallic);
+        let transmission = scalar(&self.transmission);
+        let roughness = scalar(&self.roughness);
+        let distribution = TrowbridgeReitz::from_roughness(roughness);
+        let refraction_index = color(&self.refraction_index).luminance().max(1.0);
+
+        let lobe = gen_f64();
+
+        let (wi, attenuation) = if lobe < metallic {
+            Self::sample_metallic(&wo, &base_color, &distribution)?
+        } else if lobe < metallic + (1.0 - metallic) * transmission {
+            let eta = if hit_record.front_face {
+                refraction_index
+            } else {
+                1.0 / refraction_index
+            };
+
+            let (wi, weight) = Material::sample_microfacet_dielectric(&distribution, &wo, eta)?;
+
+            // Only the transmitted light picks up the base colour
+            if wi.z < 0.0 {
+                (wi, base_color * weight)
+            } else {
+                (wi, Color::white() * weight)
+            }
+        } else {
+            Self::sample_dielectric_base(
+                &wo,
+                &base_color,
+                |diffuse, wo, wi| {
+                    self.sheen
+                        .layer(diffuse, wo, wi, hit_record.uv, &hit_record.pos)
+                },
+                scalar(&self.specular),
+                roughness,
+                refraction_index,
+                &distribution,
+            )?
+        };
+
+        Some((
+            attenuation,
+            Ray::new(hit_record.pos, basis.to_world(&wi), ray.time),
+        ))
+    }
+
+    pub fn emitted(&self, hit_record: &HitRecord) -> Color {
+        if self.two_sided || hit_record.front_face {
+            self.emission.value_at(hit_record)
+        } else {
+            Color::black()
+        }
+    }
+
+    /// Conductor tinted by the base colour through Schlick's approximation
+    fn sample_metallic(
+        wo: &Vec3,
+        base_color: &Color,
+        distribution: &TrowbridgeReitz,
+    ) -> Option<(Vec3, Color)> {
+        let (wm, wi, weight) = Material::sample_microfacet_reflection(distribution, wo)?;
+
+        let schlick = (1.0 - wo.dot(&wm).clamp(0.0, 1.0)).powi(5);
+        let reflectance = *base_color * (1.0 - schlick) + Color::white() * schlick;
+
+        Some((wi, reflectance * weight))
+    }
+
+    /// Specular reflection layered over a diffuse lobe with sheen
+    fn sample_dielectric_base(
+        wo: &Vec3,
+        base_color: &Color,
+        // Puts the sheen over a diffuse BRDF
+        sheen: impl Fn(Color, &Vec3, &Vec3) -> Color,
+        specular: f64,
+        roughness: f64,
+        refraction_index: f64,
+        distribution: &TrowbridgeReitz,
+    ) -> Option<(Vec3, Color)> {
+        let wm = Material::sample_microfacet_normal(distribution, wo);
+        let reflectance =
+            (2.0 * specular * fresnel::dielectric(wo.dot(&wm), refraction_index)).min(1.0);
+
+        if gen_f64() < reflectance {
+            let (wi, weight) = Material::reflect_microfacet(distribution, wo, &wm)?;
+            return Some((wi, Color::white() * weight));
+        }
+
+        // Cosine weighted hemisphere sample
+        let mut wi = Vec3::new(0.0, 0.0, 1.0) + Vec3::random_unit();
+        if wi.near_zero() {
+            wi = Vec3::new(0.0, 0.0, 1.0);
+        }
+        let wi = wi.normalize();
+
+        let cos_d = wi.dot(&(wi + *wo).normalize()).clamp(0.0, 1.0);
+
+        // Disney diffuse retro-reflection
+        let fd90 = 0.5 + 2.0 * roughness * cos_d * cos_d;
+        let fl = (1.0 - wi.z.clamp(0.0, 1.0)).powi(5);
+        let fv = (1.0 - wo.z.clamp(0.0, 1.0)).powi(5);
+        let diffuse = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+
+        // f * cos / pdf, the cosine pdf leaves a factor of pi
+        Some((wi, sheen(*base_color * (diffuse / PI), wo, &wi) * PI))
+    }
+}
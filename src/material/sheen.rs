@@ -0,0 +1,255 @@
+use std::{
+    f64::consts::PI,
+    sync::{Arc, OnceLock},
+};
+
+use crate::{image::color::Color, texture::Texture, vec3::Vec3};
+
+/// Samples per axis of the directional albedo tables, over cos θo and roughness
+const ALBEDO_TABLE_SIZE: usize = 32;
+
+/// Microfiber distributions for fabric sheen
+#[derive(Debug, Clone, Copy)]
+pub enum SheenDistribution {
+    /// Estevez and Kulla 2017, "Production Friendly Microfacet Sheen BRDF"
+    Charlie,
+    /// Ashikhmin and Premoze 2007, "Distribution-based BRDFs"
+    Velvet,
+}
+
+impl SheenDistribution {
+    /// Density of fibres oriented along the half vector with cosine `cos_h` to the normal
+    fn d(&self, cos_h: f64, alpha: f64) -> f64 {
+        let sin_2_h = (1.0 - cos_h * cos_h).max(1e-6);
+
+        match self {
+            SheenDistribution::Charlie => {
+                let inv_alpha = 1.0 / alpha;
+                (2.0 + inv_alpha) * sin_2_h.powf(0.5 * inv_alpha) / (2.0 * PI)
+            }
+            SheenDistribution::Velvet => {
+                let cot_2_h = cos_h * cos_h / sin_2_h;
+                let alpha_2 = alpha * alpha;
+
+                (1.0 + 4.0 * (-cot_2_h / alpha_2).exp() / (sin_2_h * sin_2_h))
+                    / (PI * (1.0 + 4.0 * alpha_2))
+            }
+        }
+    }
+
+    /// BRDF of a white sheen, for directions above the surface
+    fn brdf(&self, wo: &Vec3, wi: &Vec3, alpha: f64) -> f64 {
+        let wh = (*wo + *wi).normalize();
+        let v = 1.0 / (4.0 * (wi.z + wo.z - wi.z * wo.z));
+
+        self.d(wh.z, alpha) * v
+    }
+
+    /// Fraction of light a white sheen reflects from `cos_o`, interpolated from a table built on first use
+    fn albedo(&self, cos_o: f64, roughness: f64) -> f64 {
+        static CHARLIE: OnceLock<Vec<f64>> = OnceLock::new();
+        static VELVET: OnceLock<Vec<f64>> = OnceLock::new();
+
+        let table = match self {
+            SheenDistribution::Charlie => &CHARLIE,
+            SheenDistribution::Velvet => &VELVET,
+        }
+        .get_or_init(|| self.albedo_table());
+
+        let last = (ALBEDO_TABLE_SIZE - 1) as f64;
+        let x = cos_o.clamp(0.0, 1.0) * last;
+        let y = roughness.clamp(0.0, 1.0) * last;
+        let (x0, y0) = (
+            (x as usize).min(ALBEDO_TABLE_SIZE - 2),
+            (y as usize).min(ALBEDO_TABLE_SIZE - 2),
+        );
+        let (tx, ty) = (x - x0 as f64, y - y0 as f64);
+
+        let at = |x: usize, y: usize| table[y * ALBEDO_TABLE_SIZE + x];
+
+        (at(x0, y0) * (1.0 - tx) + at(x0 + 1, y0) * tx) * (1.0 - ty)
+            + (at(x0, y0 + 1) * (1.0 - tx) + at(x0 + 1, y0 + 1) * tx) * ty
+    }
+
+    /// Integrates the BRDF times cos θi over the hemisphere, in rows of roughness
+    fn albedo_table(&self) -> Vec<f64> {
+        const THETA_STEPS: usize = 64;
+        const PHI_STEPS: usize = 64;
+
+        let d_theta = 0.5 * PI / THETA_STEPS as f64;
+        // Symmetric about the plane of wo, so only half the turn is integrated
+        let d_phi = PI / PHI_STEPS as f64;
+        let last = (ALBEDO_TABLE_SIZE - 1) as f64;
+
+        let mut table = Vec::with_capacity(ALBEDO_TABLE_SIZE * ALBEDO_TABLE_SIZE);
+        for y in 0..ALBEDO_TABLE_SIZE {
+            let roughness = y as f64 / last;
+            let alpha = (roughness * roughness).max(1e-2);
+
+            for x in 0..ALBEDO_TABLE_SIZE {
+                let cos_o = (x as f64 / last).max(1e-3);
+                let wo = Vec3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o);
+
+                let mut albedo = 0.0;
+                for i in 0..THETA_STEPS {
+                    let (sin_theta, cos_theta) = ((i as f64 + 0.5) * d_theta).sin_cos();
+
+                    for j in 0..PHI_STEPS {
+                        let (sin_phi, cos_phi) = ((j as f64 + 0.5) * d_phi).sin_cos();
+                        let wi = Vec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+
+                        albedo += self.brdf(&wo, &wi, alpha) * cos_theta * sin_theta;
+                    }
+                }
+
+                table.push(2.0 * albedo * d_theta * d_phi);
+            }
+        }
+
+        table
+    }
+}
+
+/// Retro-reflective sheen of fabric, evaluated as a lobe over another
+#[derive(Clone)]
+pub struct Sheen {
+    distribution: SheenDistribution,
+    color: Arc<Texture>,
+    // Perceptual roughness, taken from the texture's luminance
+    roughness: Arc<Texture>,
+}
+
+impl Sheen {
+    pub fn new(
+        distribution: SheenDistribution,
+        color: Arc<Texture>,
+        roughness: Arc<Texture>,
+    ) -> Self {
+        Self {
+            distribution,
+            color,
+            roughness,
+        }
+    }
+
+    pub fn charlie(color: Color, roughness: f64) -> Self {
+        Self::new(
+            SheenDistribution::Charlie,
+            Arc::new(Texture::color(color)),
+            Arc::new(Texture::scalar(roughness)),
+        )
+    }
+
+    pub fn velvet(color: Color, roughness: f64) -> Self {
+        Self::new(
+            SheenDistribution::Velvet,
+            Arc::new(Texture::color(color)),
+            Arc::new(Texture::scalar(roughness)),
+        )
+    }
+
+    pub fn with_color(mut self, color: Arc<Texture>) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: Arc<Texture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    /// BRDF for directions in a frame with the normal along z
+    ///
+    /// Uses Neubelt and Pettineo's visibility term, which avoids the sheen's expensive masking.
+    pub fn evaluate(&self, wo: &Vec3, wi: &Vec3, uv: (f64, f64), pos: &Vec3) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::black();
+        }
+
+        let roughness = self.roughness(uv, pos);
+        let alpha = (roughness * roughness).max(1e-2);
+
+        self.color.value(uv, pos) * self.distribution.brdf(wo, wi, alpha)
+    }
+
+    /// Sheen over a `base` BRDF, which only gets the light the sheen lets through
+    ///
+    /// The base is scaled by one minus the sheen's albedo from either direction, as in glTF's
+    /// KHR_materials_sheen, so a white sheen over a white base reflects at most all the light.
+    pub fn layer(&self, base: Color, wo: &Vec3, wi: &Vec3, uv: (f64, f64), pos: &Vec3) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return base;
+        }
+
+        let color = self.color.value(uv, pos);
+        let roughness = self.roughness(uv, pos);
+
+        let strength = color.r.max(color.g).max(color.b);
+        let albedo = self
+            .distribution
+            .albedo(wo.z, roughness)
+            .max(self.distribution.albedo(wi.z, roughness));
+
+        base * (1.0 - strength * albedo).clamp(0.0, 1.0) + self.evaluate(wo, wi, uv, pos)
+    }
+
+    fn roughness(&self, uv: (f64, f64), pos: &Vec3) -> f64 {
+        self.roughness.value(uv, pos).luminance().clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reflected fraction of light from `wo`, by midpoint quadrature of the layered BRDF
+    fn layered_albedo(sheen: &Sheen, base: Color, wo: &Vec3) -> Color {
+        let (theta_steps, phi_steps) = (100, 200);
+        let d_theta = 0.5 * PI / theta_steps as f64;
+        let d_phi = 2.0 * PI / phi_steps as f64;
+        let mut albedo = Color::black();
+
+        for i in 0..theta_steps {
+            let (sin_theta, cos_theta) = ((i as f64 + 0.5) * d_theta).sin_cos();
+
+            for j in 0..phi_steps {
+                let (sin_phi, cos_phi) = ((j as f64 + 0.5) * d_phi).sin_cos();
+                let wi = Vec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+
+                let f = sheen.layer(base, wo, &wi, (0.0, 0.0), &Vec3::zero());
+                albedo += f * (cos_theta * sin_theta * d_theta * d_phi);
+            }
+        }
+
+        albedo
+    }
+
+    #[test]
+    fn white_sheen_over_white_diffuse_conserves_energy() {
+        let white_diffuse = Color::white() * (1.0 / PI);
+
+        for sheen in [
+            Sheen::charlie(Color::white(), 0.3),
+            Sheen::charlie(Color::white(), 1.0),
+            Sheen::velvet(Color::white(), 0.5),
+        ] {
+            for cos_o in [0.1_f64, 0.5, 0.9] {
+                let wo = Vec3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o);
+                let albedo = layered_albedo(&sheen, white_diffuse, &wo);
+
+                assert!(albedo.r < 1.0 + 1e-2, "{} at {}", albedo.r, cos_o);
+            }
+        }
+    }
+
+    #[test]
+    fn black_sheen_leaves_the_base() {
+        let sheen = Sheen::charlie(Color::black(), 0.5);
+        let base = Color::new(0.1, 0.2, 0.3);
+        let (wo, wi) = (Vec3::new(0.6, 0.0, 0.8), Vec3::new(0.0, 0.6, 0.8));
+
+        let f = sheen.layer(base, &wo, &wi, (0.0, 0.0), &Vec3::zero());
+
+        assert_eq!((f.r, f.g, f.b), (base.r, base.g, base.b));
+    }
+}
@@ -0,0 +1,110 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    complex::Complex,
+    image::color::Color,
+    spectrum::{self, LAMBDA_MAX, LAMBDA_MIN},
+    texture::Texture,
+    vec3::Vec3,
+};
+
+/// Wavelengths the interference is evaluated at when converting to RGB
+const FILM_SAMPLES: usize = 16;
+
+/// Thin coating whose interference tints the Fresnel reflectance
+#[derive(Clone)]
+pub struct ThinFilm {
+    // Thickness in nanometres, taken from the texture's luminance
+    thickness: Arc<Texture>,
+    refraction_index: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f64, refraction_index: f64) -> Self {
+        Self::textured(Arc::new(Texture::scalar(thickness)), refraction_index)
+    }
+
+    pub fn textured(thickness: Arc<Texture>, refraction_index: f64) -> Self {
+        Self {
+            thickness,
+            refraction_index,
+        }
+    }
+
+    /// RGB reflectance of the film between a medium of ior `eta_i` and a substrate
+    ///
+    /// `substrate` gives the substrate's complex ior at a wavelength in nanometres.
+    pub fn reflectance(
+        &self,
+        cos_i: f64,
+        eta_i: f64,
+        substrate: impl Fn(f64) -> Complex,
+        uv: (f64, f64),
+        pos: &Vec3,
+    ) -> Color {
+        let thickness = self.thickness.value(uv, pos).luminance().max(0.0);
+        let mut rgb = Color::black();
+
+        for i in 0..FILM_SAMPLES {
+            let lambda =
+                LAMBDA_MIN + (i as f64 + 0.5) * (LAMBDA_MAX - LAMBDA_MIN) / FILM_SAMPLES as f64;
+            let reflectance = airy_reflectance(
+                cos_i,
+                eta_i,
+                self.refraction_index,
+                substrate(lambda),
+                thickness,
+                lambda,
+            );
+
+            rgb += spectrum::wavelength_weight(lambda) * reflectance;
+        }
+
+        let rgb = rgb * (1.0 / FILM_SAMPLES as f64);
+
+        Color::new(
+            rgb.r.clamp(0.0, 1.0),
+            rgb.g.clamp(0.0, 1.0),
+            rgb.b.clamp(0.0, 1.0),
+        )
+    }
+}
+
+/// Reflectance of a single layer from the interference of its multiple reflections, averaged over polarisations
+fn airy_reflectance(
+    cos_1: f64,
+    eta_1: f64,
+    eta_2: f64,
+    eta_3: Complex,
+    thickness: f64,
+    lambda: f64,
+) -> f64 {
+    let sin_2_1 = (1.0 - cos_1 * cos_1).max(0.0);
+    let (n_1, n_2) = (Complex::real(eta_1), Complex::real(eta_2));
+    let cos_1 = Complex::real(cos_1);
+
+    // Complex cosines also cover total internal reflection inside the film
+    let cos_2 = (Complex::real(1.0 - sin_2_1 * (eta_1 / eta_2).powi(2))).sqrt();
+    let ratio_3 = n_1 / eta_3;
+    let cos_3 = (Complex::real(1.0) - ratio_3 * ratio_3 * Complex::real(sin_2_1)).sqrt();
+
+    let s = |n_i: Complex, cos_i: Complex, n_t: Complex, cos_t: Complex| {
+        (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t)
+    };
+    let p = |n_i: Complex, cos_i: Complex, n_t: Complex, cos_t: Complex| {
+        (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t)
+    };
+
+    // Phase difference picked up by one round trip through the film
+    let delta = Complex::real(4.0 * PI * eta_2 * thickness / lambda) * cos_2;
+    let phase = (Complex::new(0.0, 1.0) * delta).exp();
+
+    let airy = |r_12: Complex, r_23: Complex| {
+        ((r_12 + r_23 * phase) / (Complex::real(1.0) + r_12 * r_23 * phase)).norm_squared()
+    };
+
+    let r_s = airy(s(n_1, cos_1, n_2, cos_2), s(n_2, cos_2, eta_3, cos_3));
+    let r_p = airy(p(n_1, cos_1, n_2, cos_2), p(n_2, cos_2, eta_3, cos_3));
+
+    0.5 * (r_s + r_p)
+}
@@ -0,0 +1,181 @@
+use std::f64::consts::PI;
+
+use crate::vec3::Vec3;
+
+/// Trowbridge-Reitz (GGX) microfacet distribution
+///
+/// All directions are in the local shading frame, with the surface normal along z
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Self {
+            alpha_x: alpha_x.max(1e-4),
+            alpha_y: alpha_y.max(1e-4),
+        }
+    }
+
+    /// Maps perceptual roughness in [0, 1] to alpha
+    pub fn from_roughness(roughness: f64) -> Self {
+        let alpha = roughness * roughness;
+
+        Self::new(alpha, alpha)
+    }
+
+    /// Below this the distribution is treated as a perfect mirror
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    pub fn d(&self, wm: &Vec3) -> f64 {
+        let x = wm.x / self.alpha_x;
+        let y = wm.y / self.alpha_y;
+        let denom = x * x + y * y + wm.z * wm.z;
+
+        1.0 / (PI * self.alpha_x * self.alpha_y * denom * denom)
+    }
+
+    pub fn lambda(&self, w: &Vec3) -> f64 {
+        if w.z == 0.0 {
+            return f64::INFINITY;
+        }
+
+        let x = self.alpha_x * w.x;
+        let y = self.alpha_y * w.y;
+        let tan_2 = (x * x + y * y) / (w.z * w.z);
+
+        ((1.0 + tan_2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Smith masking of a single direction
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height correlated Smith masking-shadowing
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Distribution of normals visible from `wo`, back-facing ones are hidden
+    pub fn visible_d(&self, wo: &Vec3, wm: &Vec3) -> f64 {
+        if wo.z == 0.0 {
+            return 0.0;
+        }
+
+        self.g1(wo) / wo.z.abs() * self.d(wm) * wo.dot(wm).max(0.0)
+    }
+
+    /// Samples a microfacet normal visible from `wo` (Heitz 2018), `wo` must be above the surface
+    pub fn sample_visible_normal(&self, wo: &Vec3, u1: f64, u2: f64) -> Vec3 {
+        // Stretch to the hemisphere configuration
+        let vh = Vec3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).normalize();
+
+        let len_sq = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len_sq > 0.0 {
+            Vec3::new(-vh.y, vh.x, 0.0) / len_sq.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        // Sample a disk, warped towards the projected hemisphere
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        // Unstretch
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6)).normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THETA_STEPS: usize = 400;
+    const PHI_STEPS: usize = 800;
+
+    /// Midpoint rule over the upper hemisphere
+    fn integrate_hemisphere(f: impl Fn(&Vec3) -> f64) -> f64 {
+        let d_theta = 0.5 * PI / THETA_STEPS as f64;
+        let d_phi = 2.0 * PI / PHI_STEPS as f64;
+        let mut sum = 0.0;
+
+        for i in 0..THETA_STEPS {
+            let (sin_theta, cos_theta) = ((i as f64 + 0.5) * d_theta).sin_cos();
+
+            for j in 0..PHI_STEPS {
+                let (sin_phi, cos_phi) = ((j as f64 + 0.5) * d_phi).sin_cos();
+                let w = Vec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+
+                sum += f(&w) * sin_theta * d_theta * d_phi;
+            }
+        }
+
+        sum
+    }
+
+    fn distributions() -> [TrowbridgeReitz; 2] {
+        [
+            TrowbridgeReitz::new(0.5, 0.5),
+            TrowbridgeReitz::new(0.3, 0.7),
+        ]
+    }
+
+    #[test]
+    fn projected_normals_cover_unit_area() {
+        for distribution in distributions() {
+            let area = integrate_hemisphere(|wm| distribution.d(wm) * wm.z);
+
+            assert!((area - 1.0).abs() < 1e-3, "{}", area);
+        }
+    }
+
+    #[test]
+    fn visible_normals_integrate_to_one() {
+        let wo = Vec3::new(0.6, -0.3, 0.5).normalize();
+
+        for distribution in distributions() {
+            let integral = integrate_hemisphere(|wm| distribution.visible_d(&wo, wm));
+
+            assert!((integral - 1.0).abs() < 1e-3, "{}", integral);
+        }
+    }
+
+    #[test]
+    fn sampled_normals_follow_visible_d() {
+        let wo = Vec3::new(0.6, -0.3, 0.5).normalize();
+        let f = |wm: &Vec3| wm.x + 2.0 * wm.z * wm.z;
+
+        for distribution in distributions() {
+            let expected = integrate_hemisphere(|wm| f(wm) * distribution.visible_d(&wo, wm));
+
+            // Stratified samples over the unit square
+            let n = 500;
+            let mut sum = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let (u1, u2) = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+                    sum += f(&distribution.sample_visible_normal(&wo, u1, u2));
+                }
+            }
+            let estimate = sum / (n * n) as f64;
+
+            assert!(
+                (estimate - expected).abs() < 1e-3,
+                "{} {}",
+                estimate,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::vec3::Vec3;
+
+/// Orthonormal basis with `w` along a surface normal
+#[derive(Debug, Clone, Copy)]
+pub struct OrthonormalBasis {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl OrthonormalBasis {
+    /// Builds a basis around a unit vector (Duff et al. 2017)
+    pub fn from_w(w: &Vec3) -> Self {
+        let sign = 1.0_f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+
+        Self {
+            u: Vec3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x),
+            v: Vec3::new(b, sign + w.y * w.y * a, -w.y),
+            w: *w,
+        }
+    }
+
+    /// Builds a basis around a unit vector with `u` along a tangent, falling back to `from_w` if it is degenerate
+    pub fn from_w_and_tangent(w: &Vec3, tangent: &Vec3) -> Self {
+        let u = *tangent - w.dot(tangent) * *w;
+
+        if u.near_zero() {
+            return Self::from_w(w);
+        }
+
+        let u = u.normalize();
+
+        Self {
+            u,
+            v: w.cross(&u),
+            w: *w,
+        }
+    }
+
+    /// Converts from local coordinates (z along `w`) to world space
+    pub fn to_world(&self, local: &Vec3) -> Vec3 {
+        local.x * self.u + local.y * self.v + local.z * self.w
+    }
+
+    pub fn to_local(&self, world: &Vec3) -> Vec3 {
+        Vec3::new(world.dot(&self.u), world.dot(&self.v), world.dot(&self.w))
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+/// Snapshot of how far a render has got
+#[derive(Debug, Clone, Copy)]
+pub struct RenderProgress {
+    pub chunks_done: usize,
+    pub total_chunks: usize,
+    // Camera rays traced so far
+    pub samples_done: usize,
+    pub elapsed: Duration,
+}
+
+impl RenderProgress {
+    pub fn fraction(&self) -> f64 {
+        if self.total_chunks == 0 {
+            1.0
+        } else {
+            self.chunks_done as f64 / self.total_chunks as f64
+        }
+    }
+
+    pub fn samples_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+
+        if seconds > 0.0 {
+            self.samples_done as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Estimated time until the render finishes, assuming the remaining chunks take as long as the finished ones
+    pub fn eta(&self) -> Option<Duration> {
+        if self.chunks_done == 0 {
+            return None;
+        }
+
+        let per_chunk = self.elapsed.as_secs_f64() / self.chunks_done as f64;
+        let remaining = self.total_chunks.saturating_sub(self.chunks_done);
+
+        Some(Duration::from_secs_f64(per_chunk * remaining as f64))
+    }
+}
+
+/// Receives progress callbacks from a render, possibly from several threads at once
+pub trait ProgressSink: Sync {
+    fn started(&self, _progress: &RenderProgress) {}
+
+    fn chunk_done(&self, _progress: &RenderProgress) {}
+
+    fn finished(&self, _progress: &RenderProgress) {}
+}
+
+/// Ignores all progress
+pub struct SilentProgress;
+
+impl ProgressSink for SilentProgress {}
+
+/// Draws a progress bar on stdout
+pub struct TerminalProgress {
+    bar_width: usize,
+}
+
+impl Default for TerminalProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TerminalProgress {
+    pub fn new() -> Self {
+        Self { bar_width: 40 }
+    }
+
+    fn draw(&self, progress: &RenderProgress) {
+        let filled = (progress.fraction() * self.bar_width as f64) as usize;
+        let eta = match progress.eta() {
+            Some(eta) => format!("{:.0}s", eta.as_secs_f64()),
+            None => "?".to_string(),
+        };
+
+        let mut stdout = io::stdout().lock();
+        let result = write!(
+            stdout,
+            "\r[{}{}] {}/{} chunks, {:.2} Msamples/s, ETA {} ",
+            "#".repeat(filled),
+            " ".repeat(self.bar_width - filled),
+            progress.chunks_done,
+            progress.total_chunks,
+            progress.samples_per_second() / 1_000_000.0,
+            eta
+        )
+        .and_then(|_| stdout.flush());
+
+        if let Err(e) = result {
+            eprintln!("Warning: Could not write progress: {}", e);
+        }
+    }
+}
+
+impl ProgressSink for TerminalProgress {
+    fn started(&self, progress: &RenderProgress) {
+        println!(
+            "Starting render: {} chunks to process",
+            progress.total_chunks
+        );
+    }
+
+    fn chunk_done(&self, progress: &RenderProgress) {
+        self.draw(progress);
+    }
+
+    fn finished(&self, progress: &RenderProgress) {
+        self.draw(progress);
+        println!("\nDone in {:.2} seconds", progress.elapsed.as_secs_f64());
+    }
+}
@@ -1,9 +1,24 @@
 use crate::vec3::Vec3;
 
+/// Rays offset by one pixel in x and y, tracking how a camera ray's footprint spreads
+#[derive(Debug, Clone, Copy)]
+pub struct RayDifferentials {
+    pub rx_origin: Vec3,
+    pub rx_direction: Vec3,
+    pub ry_origin: Vec3,
+    pub ry_direction: Vec3,
+}
+
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
     pub time: f64,
+    // Wavelength in nanometres once a dispersive material has picked one for the path
+    pub wavelength: Option<f64>,
+    // Colour channel once a chromatic medium has restricted an RGB path to one
+    pub channel: Option<usize>,
+    // Only kept along camera rays and specular bounces
+    pub differentials: Option<RayDifferentials>,
 }
 
 #[allow(dead_code)]
@@ -13,9 +28,27 @@ impl Ray {
             origin,
             direction,
             time,
+            wavelength: None,
+            channel: None,
+            differentials: None,
         }
     }
 
+    pub const fn with_wavelength(mut self, wavelength: Option<f64>) -> Self {
+        self.wavelength = wavelength;
+        self
+    }
+
+    pub const fn with_channel(mut self, channel: Option<usize>) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    pub const fn with_differentials(mut self, differentials: Option<RayDifferentials>) -> Self {
+        self.differentials = differentials;
+        self
+    }
+
     pub fn at(&self, t: f64) -> Vec3 {
         self.origin + t * self.direction
     }
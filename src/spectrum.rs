@@ -0,0 +1,314 @@
+use std::{
+    ops::{Add, AddAssign, Mul},
+    sync::OnceLock,
+};
+
+use crate::{image::color::Color, random::gen_range_f64};
+
+/// Visible range in nanometres
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+pub fn sample_wavelength() -> f64 {
+    gen_range_f64(LAMBDA_MIN, LAMBDA_MAX)
+}
+
+/// CIE 1931 colour matching functions, using the multi-lobe fit of Wyman et al. 2013
+pub fn cie_xyz(lambda: f64) -> (f64, f64, f64) {
+    fn lobe(lambda: f64, mean: f64, sigma_below: f64, sigma_above: f64) -> f64 {
+        let sigma = if lambda < mean {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        let t = (lambda - mean) / sigma;
+
+        (-0.5 * t * t).exp()
+    }
+
+    let x = 1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8);
+
+    (x, y, z)
+}
+
+/// Converts CIE XYZ to linear sRGB (D65 white point)
+pub fn xyz_to_rgb(x: f64, y: f64, z: f64) -> Color {
+    Color::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+fn wavelength_to_rgb(lambda: f64) -> Color {
+    let (x, y, z) = cie_xyz(lambda);
+    xyz_to_rgb(x, y, z)
+}
+
+/// Colour carried by a path at a uniformly sampled wavelength
+///
+/// Normalised so that averaging over all wavelengths gives white. Out of gamut wavelengths have negative channels.
+pub fn wavelength_weight(lambda: f64) -> Color {
+    static AVERAGE: OnceLock<Color> = OnceLock::new();
+
+    let average = AVERAGE.get_or_init(|| {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let mut sum = Color::black();
+
+        for i in 0..steps {
+            sum += wavelength_to_rgb(LAMBDA_MIN + i as f64 + 0.5);
+        }
+
+        sum * (1.0 / steps as f64)
+    });
+
+    let rgb = wavelength_to_rgb(lambda);
+
+    Color::new(rgb.r / average.r, rgb.g / average.g, rgb.b / average.b)
+}
+
+/// Planck's law, spectral radiance of a blackbody at a wavelength in nanometres
+pub fn blackbody(lambda: f64, temperature: f64) -> f64 {
+    const C: f64 = 299_792_458.0;
+    const H: f64 = 6.626_070_15e-34;
+    const K_B: f64 = 1.380_649e-23;
+
+    let lambda = lambda * 1e-9;
+
+    2.0 * H * C * C / (lambda.powi(5) * ((H * C / (lambda * K_B * temperature)).exp() - 1.0))
+}
+
+/// Linear sRGB colour of a blackbody at `temperature` Kelvin, scaled to a luminance of one
+pub fn blackbody_color(temperature: f64) -> Color {
+    let mut xyz = (0.0, 0.0, 0.0);
+
+    for lambda in LAMBDA_MIN as usize..LAMBDA_MAX as usize {
+        let lambda = lambda as f64 + 0.5;
+        let radiance = blackbody(lambda, temperature);
+        let (x, y, z) = cie_xyz(lambda);
+
+        xyz.0 += radiance * x;
+        xyz.1 += radiance * y;
+        xyz.2 += radiance * z;
+    }
+
+    // Very low temperatures fall slightly outside the sRGB gamut
+    let rgb = xyz_to_rgb(xyz.0, xyz.1, xyz.2);
+    let rgb = Color::new(rgb.r.max(0.0), rgb.g.max(0.0), rgb.b.max(0.0));
+
+    rgb * (1.0 / rgb.luminance())
+}
+
+/// Value at `lambda` of a smooth spectrum matching a linear RGB colour
+pub fn rgb_to_spectrum(color: &Color, lambda: f64) -> f64 {
+    smits::rgb_to_spectrum(color, lambda)
+}
+
+/// Wavelengths traced together by each path in spectral mode
+pub const SPECTRUM_SAMPLES: usize = 4;
+
+/// Hero wavelength with the others spaced evenly across the visible range (Wilkie et al. 2014)
+#[derive(Debug, Clone, Copy)]
+pub struct SampledWavelengths {
+    pub lambda: [f64; SPECTRUM_SAMPLES],
+}
+
+impl SampledWavelengths {
+    pub fn sample() -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = sample_wavelength() - LAMBDA_MIN;
+
+        Self {
+            lambda: std::array::from_fn(|i| {
+                LAMBDA_MIN + (hero + i as f64 * range / SPECTRUM_SAMPLES as f64) % range
+            }),
+        }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+}
+
+/// Spectrum evaluated at a set of sampled wavelengths
+#[derive(Debug, Clone, Copy)]
+pub struct SampledSpectrum {
+    pub values: [f64; SPECTRUM_SAMPLES],
+}
+
+impl SampledSpectrum {
+    pub fn constant(value: f64) -> Self {
+        Self {
+            values: [value; SPECTRUM_SAMPLES],
+        }
+    }
+
+    /// Upsamples a linear RGB colour with Smits' method, values above one scale the spectrum
+    pub fn from_rgb(color: &Color, wavelengths: &SampledWavelengths) -> Self {
+        Self {
+            values: wavelengths
+                .lambda
+                .map(|lambda| rgb_to_spectrum(color, lambda)),
+        }
+    }
+
+    pub fn to_rgb(&self, wavelengths: &SampledWavelengths) -> Color {
+        let mut rgb = Color::black();
+
+        for (value, lambda) in self.values.iter().zip(wavelengths.lambda) {
+            rgb += wavelength_weight(lambda) * *value;
+        }
+
+        rgb * (1.0 / SPECTRUM_SAMPLES as f64)
+    }
+
+    /// Drops all but the hero wavelength, for paths whose direction depends on wavelength
+    pub fn hero_only(&self) -> Self {
+        let mut values = [0.0; SPECTRUM_SAMPLES];
+        values[0] = self.values[0] * SPECTRUM_SAMPLES as f64;
+
+        Self { values }
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            values: std::array::from_fn(|i| self.values[i] + rhs.values[i]),
+        }
+    }
+}
+
+impl AddAssign for SampledSpectrum {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            values: std::array::from_fn(|i| self.values[i] * rhs.values[i]),
+        }
+    }
+}
+
+mod smits {
+    use crate::image::color::Color;
+
+    use super::LAMBDA_MIN;
+
+    /// Upper end of the table, longer wavelengths use the last bin
+    const TABLE_MAX: f64 = 720.0;
+
+    const WHITE: [f64; 10] = [
+        1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+    ];
+    const CYAN: [f64; 10] = [
+        0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+    ];
+    const MAGENTA: [f64; 10] = [
+        1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+    ];
+    const YELLOW: [f64; 10] = [
+        0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+    ];
+    const RED: [f64; 10] = [
+        0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+    ];
+    const GREEN: [f64; 10] = [
+        0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+    ];
+    const BLUE: [f64; 10] = [
+        1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+    ];
+
+    /// Smits 1999, "An RGB-to-Spectrum Conversion for Reflectances"
+    pub fn rgb_to_spectrum(color: &Color, lambda: f64) -> f64 {
+        let bin = (((lambda - LAMBDA_MIN) / (TABLE_MAX - LAMBDA_MIN) * 10.0) as usize).min(9);
+        let (r, g, b) = (color.r, color.g, color.b);
+
+        // White for the shared amount, then the secondary and primary colours for the rest
+        if r <= g && r <= b {
+            if g <= b {
+                r * WHITE[bin] + (g - r) * CYAN[bin] + (b - g) * BLUE[bin]
+            } else {
+                r * WHITE[bin] + (b - r) * CYAN[bin] + (g - b) * GREEN[bin]
+            }
+        } else if g <= r && g <= b {
+            if r <= b {
+                g * WHITE[bin] + (r - g) * MAGENTA[bin] + (b - r) * BLUE[bin]
+            } else {
+                g * WHITE[bin] + (b - g) * MAGENTA[bin] + (r - b) * RED[bin]
+            }
+        } else if r <= g {
+            b * WHITE[bin] + (r - b) * YELLOW[bin] + (g - r) * GREEN[bin]
+        } else {
+            b * WHITE[bin] + (g - b) * YELLOW[bin] + (r - g) * RED[bin]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Colour seen for a spectrum, averaging `wavelength_weight` over the visible range
+    fn spectrum_to_rgb(spectrum: impl Fn(f64) -> f64) -> Color {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let mut sum = Color::black();
+
+        for i in 0..steps {
+            let lambda = LAMBDA_MIN + i as f64 + 0.5;
+            sum += wavelength_weight(lambda) * spectrum(lambda);
+        }
+
+        sum * (1.0 / steps as f64)
+    }
+
+    #[test]
+    fn white_upsamples_to_a_flat_spectrum() {
+        for i in 0..=40 {
+            let lambda = LAMBDA_MIN + 10.0 * i as f64;
+            let value = rgb_to_spectrum(&Color::white(), lambda);
+
+            assert!((value - 1.0).abs() < 1e-3, "{} at {} nm", value, lambda);
+        }
+    }
+
+    #[test]
+    fn upsampling_scales_with_the_colour() {
+        let color = Color::new(0.7, 0.2, 0.4);
+
+        for lambda in [400.0, 500.0, 600.0, 700.0] {
+            let scaled = rgb_to_spectrum(&(color * 0.5), lambda);
+
+            assert!((scaled - 0.5 * rgb_to_spectrum(&color, lambda)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn upsampled_colours_round_trip() {
+        for color in [
+            Color::new(0.5, 0.5, 0.5),
+            Color::new(0.8, 0.3, 0.1),
+            Color::new(0.2, 0.6, 0.3),
+            Color::new(0.1, 0.2, 0.7),
+        ] {
+            let rgb = spectrum_to_rgb(|lambda| rgb_to_spectrum(&color, lambda));
+            let error = (rgb.r - color.r)
+                .abs()
+                .max((rgb.g - color.g).abs())
+                .max((rgb.b - color.b).abs());
+
+            assert!(error < 0.01, "{:?} became {:?}", color, rgb);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use crate::{
+    hittable::HitRecord,
     image::{color::Color, ExtImage},
     perlin::Perlin,
     vec3::Vec3,
@@ -47,6 +48,11 @@ impl Texture {
         }
     }
 
+    /// Image texture with its own filter and wrap mode
+    pub fn image_from(image: ExtImage) -> Self {
+        Self::Image { image }
+    }
+
     pub fn noise(scale: f64) -> Self {
         Self::Noise {
             noise: Perlin::new(),
@@ -63,43 +69,72 @@ impl Texture {
             albedo: Color::new(r, g, b),
         }
     }
+
+    /// Grey constant, for parameters read through the texture's luminance
+    pub fn scalar(value: f64) -> Self {
+        Self::from_rgb(value, value, value)
+    }
 }
 
 impl Texture {
     pub fn value(&self, uv: (f64, f64), pos: &Vec3) -> Color {
+        self.value_footprint(uv, pos, 0.0)
+    }
+
+    /// Value at a hit, filtered over its pixel footprint when it has one
+    pub fn value_at(&self, hit_record: &HitRecord) -> Color {
+        let footprint = hit_record.du.max(hit_record.dv);
+
+        self.value_footprint(hit_record.uv, &hit_record.pos, footprint)
+    }
+
+    /// Value filtered over a footprint of `width` in uv, image textures pick their mip level from it
+    pub fn value_footprint(&self, uv: (f64, f64), pos: &Vec3, width: f64) -> Color {
         match self {
             Texture::Checker {
                 inv_scale,
                 even,
                 odd,
-            } => Self::checker_value(*inv_scale, even, odd, uv, pos),
-            Texture::Image { image } => Self::image_value(image, uv, pos),
+            } => Self::checker_select(*inv_scale, even, odd, pos).value_footprint(uv, pos, width),
+            Texture::Image { image } => Self::image_value(image, uv, width),
             Texture::Noise { noise, scale } => Self::noise_value(noise, *scale, uv, pos),
             Texture::Color { albedo } => albedo.clone(),
         }
     }
 
-    fn checker_value(
+    /// Opacity for cutouts, the alpha channel of images and the luminance of other textures
+    pub fn opacity(&self, uv: (f64, f64), pos: &Vec3) -> f64 {
+        match self {
+            Texture::Checker {
+                inv_scale,
+                even,
+                odd,
+            } => Self::checker_select(*inv_scale, even, odd, pos).opacity(uv, pos),
+            Texture::Image { image } => Self::image_opacity(image, uv),
+            _ => self.value(uv, pos).luminance(),
+        }
+    }
+
+    fn checker_select<'a>(
         inv_scale: f64,
-        even: &Arc<Texture>,
-        odd: &Arc<Texture>,
-        uv: (f64, f64),
+        even: &'a Arc<Texture>,
+        odd: &'a Arc<Texture>,
         pos: &Vec3,
-    ) -> Color {
+    ) -> &'a Arc<Texture> {
         let x = (inv_scale * pos.x).floor() as i32;
         let y = (inv_scale * pos.y).floor() as i32;
         let z = (inv_scale * pos.z).floor() as i32;
 
         if (x + y + z) % 2 == 0 {
-            even.value(uv, pos)
+            even
         } else {
-            odd.value(uv, pos)
+            odd
         }
     }
 
-    fn image_value(image: &ExtImage, uv: (f64, f64), _pos: &Vec3) -> Color {
+    fn image_value(image: &ExtImage, uv: (f64, f64), width: f64) -> Color {
         // Debug color (cyan) if no image
-        if image.height() <= 0 {
+        if image.height() == 0 {
             return Color {
                 r: 0.0,
                 g: 1.0,
@@ -107,20 +142,17 @@ impl Texture {
             };
         }
 
-        let u = uv.0.clamp(0.0, 1.0);
-        let v = 1.0 - uv.1.clamp(0.0, 1.0);
+        let [r, g, b, _] = image.sample_footprint(uv, width);
 
-        let i = (u * image.width() as f64) as usize;
-        let j = (v * image.height() as f64) as usize;
-        let pixel = image.pixel_data(i, j);
+        Color::new(r, g, b)
+    }
 
-        let color_scale = 1.0 / 255.0;
+    fn image_opacity(image: &ExtImage, uv: (f64, f64)) -> f64 {
+        if image.height() == 0 {
+            return 1.0;
+        }
 
-        Color::new(
-            color_scale * pixel[0] as f64,
-            color_scale * pixel[1] as f64,
-            color_scale * pixel[2] as f64,
-        )
+        image.sample(uv)[3]
     }
 
     fn noise_value(noise: &Perlin, scale: f64, _uv: (f64, f64), pos: &Vec3) -> Color {